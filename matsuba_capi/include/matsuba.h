@@ -0,0 +1,88 @@
+#ifndef MATSUBA_H
+#define MATSUBA_H
+
+/* Generated with cbindgen from matsuba_capi, do not edit by hand. Regenerate with `just capi-header` */
+
+#include <stdbool.h>
+#include <stdint.h>
+
+/*
+ Romaji converter using the built-in conversion table
+ */
+typedef struct MatsubaConverter MatsubaConverter;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ Creates a converter, to be released with [`matsuba_converter_free`]
+ */
+MatsubaConverter *matsuba_converter_new(void);
+
+/*
+ Releases a converter
+
+ # Safety
+
+ `converter` must have been returned by [`matsuba_converter_new`] and not released already.
+ Null is ignored.
+ */
+void matsuba_converter_free(MatsubaConverter *converter);
+
+/*
+ Feeds a unicode scalar value to the converter at the caret
+
+ Returns false if the value is not a valid character or is nul.
+
+ # Safety
+
+ `converter` must be a valid converter or null.
+ */
+bool matsuba_converter_input_char(MatsubaConverter *converter, uint32_t ch);
+
+/*
+ Deletes the character before the caret, or the last key of any pending romaji
+
+ # Safety
+
+ `converter` must be a valid converter or null.
+ */
+void matsuba_converter_del_char(MatsubaConverter *converter);
+
+/*
+ Text currently being composed, including any pending romaji
+
+ Returns null if `converter` is null.
+
+ # Safety
+
+ `converter` must be a valid converter or null.
+ */
+char *matsuba_converter_preedit(const MatsubaConverter *converter);
+
+/*
+ Takes the composed text out of the converter, leaving it empty
+
+ Returns null if `converter` is null.
+
+ # Safety
+
+ `converter` must be a valid converter or null.
+ */
+char *matsuba_converter_accept(MatsubaConverter *converter);
+
+/*
+ Releases a string returned by this library
+
+ # Safety
+
+ `s` must have been returned by this library and not released already. Null is ignored.
+ */
+void matsuba_string_free(char *s);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* MATSUBA_H */
@@ -0,0 +1,165 @@
+//! C interface to the matsuba romaji converter
+//!
+//! The header for this interface is `include/matsuba.h`. Strings returned by this library are
+//! utf-8 and owned by the caller, who must release them with [`matsuba_string_free`].
+
+use std::ffi::{c_char, CString};
+
+use matsuba_common::converter::Converter;
+
+/// Romaji converter using the built-in conversion table
+pub struct MatsubaConverter(Converter);
+
+fn to_c_string(s: String) -> *mut c_char {
+    // input containing nul is rejected, so converted text never contains it
+    CString::new(s)
+        .map(CString::into_raw)
+        .unwrap_or(std::ptr::null_mut())
+}
+
+/// Creates a converter, to be released with [`matsuba_converter_free`]
+#[no_mangle]
+pub extern "C" fn matsuba_converter_new() -> *mut MatsubaConverter {
+    Box::into_raw(Box::new(MatsubaConverter(Converter::new())))
+}
+
+/// Releases a converter
+///
+/// # Safety
+///
+/// `converter` must have been returned by [`matsuba_converter_new`] and not released already.
+/// Null is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn matsuba_converter_free(converter: *mut MatsubaConverter) {
+    if !converter.is_null() {
+        drop(Box::from_raw(converter));
+    }
+}
+
+/// Feeds a unicode scalar value to the converter at the caret
+///
+/// Returns false if the value is not a valid character or is nul.
+///
+/// # Safety
+///
+/// `converter` must be a valid converter or null.
+#[no_mangle]
+pub unsafe extern "C" fn matsuba_converter_input_char(
+    converter: *mut MatsubaConverter,
+    ch: u32,
+) -> bool {
+    let converter = match converter.as_mut() {
+        Some(converter) => converter,
+        None => return false,
+    };
+    match char::from_u32(ch) {
+        Some(ch) if ch != '\0' => {
+            converter.0.input_char(ch);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Deletes the character before the caret, or the last key of any pending romaji
+///
+/// # Safety
+///
+/// `converter` must be a valid converter or null.
+#[no_mangle]
+pub unsafe extern "C" fn matsuba_converter_del_char(converter: *mut MatsubaConverter) {
+    if let Some(converter) = converter.as_mut() {
+        converter.0.del_char();
+    }
+}
+
+/// Text currently being composed, including any pending romaji
+///
+/// Returns null if `converter` is null.
+///
+/// # Safety
+///
+/// `converter` must be a valid converter or null.
+#[no_mangle]
+pub unsafe extern "C" fn matsuba_converter_preedit(
+    converter: *const MatsubaConverter,
+) -> *mut c_char {
+    match converter.as_ref() {
+        Some(converter) => to_c_string(converter.0.preedit().to_string()),
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// Takes the composed text out of the converter, leaving it empty
+///
+/// Returns null if `converter` is null.
+///
+/// # Safety
+///
+/// `converter` must be a valid converter or null.
+#[no_mangle]
+pub unsafe extern "C" fn matsuba_converter_accept(converter: *mut MatsubaConverter) -> *mut c_char {
+    match converter.as_mut() {
+        Some(converter) => to_c_string(converter.0.accept()),
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// Releases a string returned by this library
+///
+/// # Safety
+///
+/// `s` must have been returned by this library and not released already. Null is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn matsuba_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::ffi::CStr;
+
+    use super::*;
+
+    unsafe fn take(s: *mut c_char) -> String {
+        let owned = CStr::from_ptr(s).to_str().unwrap().to_owned();
+        matsuba_string_free(s);
+        owned
+    }
+
+    #[test]
+    fn convert() {
+        unsafe {
+            let converter = matsuba_converter_new();
+            for ch in "nihongo".chars() {
+                assert!(matsuba_converter_input_char(converter, ch as u32));
+            }
+            assert!(!matsuba_converter_input_char(converter, 0));
+            assert!(!matsuba_converter_input_char(converter, 0xD800));
+            assert_eq!(take(matsuba_converter_preedit(converter)), "にほんご");
+
+            matsuba_converter_del_char(converter);
+            assert_eq!(take(matsuba_converter_accept(converter)), "にほん");
+            assert_eq!(take(matsuba_converter_preedit(converter)), "");
+            matsuba_converter_free(converter);
+        }
+    }
+
+    #[test]
+    fn null() {
+        unsafe {
+            assert!(!matsuba_converter_input_char(
+                std::ptr::null_mut(),
+                'a' as u32
+            ));
+            matsuba_converter_del_char(std::ptr::null_mut());
+            assert!(matsuba_converter_preedit(std::ptr::null()).is_null());
+            assert!(matsuba_converter_accept(std::ptr::null_mut()).is_null());
+            matsuba_converter_free(std::ptr::null_mut());
+            matsuba_string_free(std::ptr::null_mut());
+        }
+    }
+}
@@ -0,0 +1,76 @@
+/* Exercises the matsuba C interface, run with `just capi-test` */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "matsuba.h"
+
+/* like assert, but evaluated even when built with NDEBUG */
+#define CHECK(condition)                                                           \
+    do {                                                                           \
+        if (!(condition)) {                                                        \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__,       \
+                    #condition);                                                   \
+            exit(1);                                                               \
+        }                                                                          \
+    } while (0)
+
+static void input(MatsubaConverter *converter, const char *romaji)
+{
+    for (const char *ch = romaji; *ch != '\0'; ch++) {
+        CHECK(matsuba_converter_input_char(converter, (uint32_t)*ch));
+    }
+}
+
+static void expect(char *actual, const char *expected)
+{
+    CHECK(actual != NULL);
+    if (strcmp(actual, expected) != 0) {
+        fprintf(stderr, "expected %s, got %s\n", expected, actual);
+        exit(1);
+    }
+    matsuba_string_free(actual);
+}
+
+int main(void)
+{
+    MatsubaConverter *converter = matsuba_converter_new();
+    CHECK(converter != NULL);
+
+    input(converter, "nihongo");
+    expect(matsuba_converter_preedit(converter), "にほんご");
+
+    /* pending romaji is part of the preedit */
+    input(converter, "k");
+    expect(matsuba_converter_preedit(converter), "にほんごk");
+    matsuba_converter_del_char(converter);
+    matsuba_converter_del_char(converter);
+    expect(matsuba_converter_preedit(converter), "にほん");
+
+    /* upper case romaji is converted to katakana */
+    input(converter, "KATAKANA");
+    expect(matsuba_converter_accept(converter), "にほんカタカナ");
+    expect(matsuba_converter_preedit(converter), "");
+
+    /* characters outside of ascii are accepted as is */
+    CHECK(matsuba_converter_input_char(converter, 0x6F22));
+    expect(matsuba_converter_accept(converter), "漢");
+
+    /* nul and surrogates are rejected */
+    CHECK(!matsuba_converter_input_char(converter, 0));
+    CHECK(!matsuba_converter_input_char(converter, 0xD800));
+
+    /* null is ignored everywhere */
+    CHECK(!matsuba_converter_input_char(NULL, 'a'));
+    matsuba_converter_del_char(NULL);
+    CHECK(matsuba_converter_preedit(NULL) == NULL);
+    CHECK(matsuba_converter_accept(NULL) == NULL);
+    matsuba_string_free(NULL);
+    matsuba_converter_free(NULL);
+
+    matsuba_converter_free(converter);
+
+    printf("ok\n");
+    return 0;
+}
@@ -1,6 +1,9 @@
 use log::{debug, info};
 use matsuba_grpc::matsuba_client::MatsubaClient;
-use matsuba_grpc::{ConvertRequest, FetchRequest, GetStateRequest, SetStateHenkanRequest};
+use matsuba_grpc::{
+    AddWordRequest, ConvertRequest, FetchRequest, GetStateRequest, ListWordsRequest,
+    RemoveWordRequest, RomajiRequest, SetStateHenkanRequest,
+};
 use pino_argparse::{Cli, Command, Flag, FlagParse};
 use tonic::Request;
 
@@ -15,6 +18,7 @@ pub type BoxResult<T> = Result<T, Box<dyn Error>>;
 pub enum CliError {
     WrongArgCount,
     InvalidTag(String),
+    InvalidAction(String),
 }
 
 impl Error for CliError {}
@@ -24,6 +28,7 @@ impl fmt::Display for CliError {
         match self {
             CliError::WrongArgCount => write!(f, "Wrong number of arguments"),
             CliError::InvalidTag(tag) => write!(f, "Invalid tag passed: {}", tag),
+            CliError::InvalidAction(action) => write!(f, "Invalid action passed: {}", action),
         }
     }
 }
@@ -34,9 +39,12 @@ matsucli [-v] <command>
 
 COMMANDS:
 help
-fetch
+fetch [--file <path>]
 state <get|henkan|muhenkan>
 convert <phrase>
+romaji <kana>
+dict <add|remove> <reading> <word>
+dict list
 ";
 
 static CONNECTION_STRING: &str = "http://[::1]:10000";
@@ -56,10 +64,16 @@ pub fn runcli() -> BoxResult<()> {
                 command_name: "fetch",
                 desc: "fetch word lists",
                 handler: handle_fetch,
-                flags: vec![Flag::new("tags")
-                    .short('t')
-                    .desc("specify which tags should be included or not included")
-                    .parameter()],
+                flags: vec![
+                    Flag::new("tags")
+                        .short('t')
+                        .desc("specify which tags should be included or not included")
+                        .parameter(),
+                    Flag::new("file")
+                        .short('f')
+                        .desc("import a local JMdict file instead of downloading it")
+                        .parameter(),
+                ],
             },
             Command {
                 command_name: "convert",
@@ -75,6 +89,26 @@ pub fn runcli() -> BoxResult<()> {
                         .parameter(),
                 ],
             },
+            Command {
+                command_name: "romaji",
+                desc: "convert kana back to romaji",
+                handler: handle_romaji,
+                flags: vec![
+                    Flag::new("romanization")
+                        .short('r')
+                        .desc("romanization system to use (hepburn, kunrei, nihonshiki or mixed)")
+                        .parameter(),
+                    Flag::new("macron")
+                        .short('m')
+                        .desc("mark long vowels with macrons"),
+                ],
+            },
+            Command {
+                command_name: "dict",
+                desc: "add, remove or list words in the user dictionary",
+                handler: handle_dict,
+                flags: vec![],
+            },
             Command {
                 command_name: "state",
                 desc: "query state of matsuba",
@@ -132,11 +166,17 @@ fn handle_fetch(flagparse: FlagParse) -> BoxResult<()> {
         .map(|x| x.to_string())
         .collect::<Vec<String>>();
 
+    // the daemon may run in another directory, so pass it an absolute path
+    let path = match flagparse.get_flag_value::<String>("file") {
+        Some(file) => std::fs::canonicalize(file)?.to_string_lossy().into_owned(),
+        None => String::new(),
+    };
+
     Runtime::new()?.block_on(async {
         let mut client = MatsubaClient::connect(CONNECTION_STRING).await.unwrap();
 
         let _response = client
-            .fetch(Request::new(FetchRequest { tags }))
+            .fetch(Request::new(FetchRequest { tags, path }))
             .await
             .unwrap();
     });
@@ -162,6 +202,96 @@ fn handle_convert(flagparse: FlagParse) -> BoxResult<()> {
         for converted in response.converted {
             println!("{}", converted);
         }
+        if !response.pending.is_empty() {
+            eprintln!("incomplete input, '{}' was not converted", response.pending);
+        }
+    });
+    Ok(())
+}
+
+fn handle_romaji(flagparse: FlagParse) -> BoxResult<()> {
+    if flagparse.args.len() != 1 {
+        return Err(Box::new(CliError::WrongArgCount));
+    }
+
+    Runtime::new()?.block_on(async {
+        let mut client = MatsubaClient::connect(CONNECTION_STRING).await.unwrap();
+
+        let response = client
+            .romaji(Request::new(RomajiRequest {
+                kana: flagparse.args.get(0).unwrap().to_string(),
+                romanization: flagparse
+                    .get_flag_value::<String>("romanization")
+                    .unwrap_or_default(),
+                mark_long_vowels: flagparse.get_flag("macron"),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        println!("{}", response.romaji);
+    });
+    Ok(())
+}
+
+fn handle_dict(flagparse: FlagParse) -> BoxResult<()> {
+    enum Action {
+        Add(String, String),
+        Remove(String, String),
+        List,
+    }
+
+    let args = flagparse
+        .args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<&str>>();
+    let action = match args.as_slice() {
+        ["add", reading, word] => Action::Add(reading.to_string(), word.to_string()),
+        ["remove", reading, word] => Action::Remove(reading.to_string(), word.to_string()),
+        ["list"] => Action::List,
+        ["add" | "remove" | "list", ..] | [] => return Err(Box::new(CliError::WrongArgCount)),
+        [action, ..] => return Err(Box::new(CliError::InvalidAction(action.to_string()))),
+    };
+
+    Runtime::new()?.block_on(async {
+        let mut client = MatsubaClient::connect(CONNECTION_STRING).await.unwrap();
+
+        match action {
+            Action::Add(reading, word) => {
+                let response = client
+                    .add_word(Request::new(AddWordRequest { reading, word }))
+                    .await
+                    .unwrap()
+                    .into_inner();
+
+                if !response.added {
+                    eprintln!("word is already in the user dictionary");
+                }
+            }
+            Action::Remove(reading, word) => {
+                let response = client
+                    .remove_word(Request::new(RemoveWordRequest { reading, word }))
+                    .await
+                    .unwrap()
+                    .into_inner();
+
+                if !response.removed {
+                    eprintln!("word is not in the user dictionary");
+                }
+            }
+            Action::List => {
+                let response = client
+                    .list_words(Request::new(ListWordsRequest {}))
+                    .await
+                    .unwrap()
+                    .into_inner();
+
+                for word in response.words {
+                    println!("{}\t{}", word.reading, word.word);
+                }
+            }
+        }
     });
     Ok(())
 }
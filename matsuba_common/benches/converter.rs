@@ -0,0 +1,95 @@
+//! Per request cost of converting romaji before and after the state machine was shared
+//!
+//! Before, every request built its own state machine with a `HashMap` of transitions per state.
+//! The compact state machine built for every request is also measured, to tell apart what the
+//! new layout saves from what sharing saves.
+//!
+//! Run with `cargo bench -p matsuba_common`.
+
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::sync::Arc;
+use std::time::Instant;
+
+use matsuba_common::conversion::ConversionTable;
+use matsuba_common::converter::{Converter, Dfa};
+
+const REQUESTS: u32 = 10_000;
+const INPUT: &str = "watashihanihongowobenkyoushiteimasu";
+
+/// State of the state machine that was built for every request before it was shared
+struct State {
+    accepting: Option<(String, String)>,
+    transitions: HashMap<char, usize>,
+}
+
+/// Copy of the builder of the state machine used before it was shared
+fn build_hashmap_dfa(table: &ConversionTable) -> Vec<State> {
+    let new_state = || State {
+        accepting: None,
+        transitions: HashMap::new(),
+    };
+    let mut state_pool: Vec<State> = vec![new_state()];
+
+    for rule in table.rules() {
+        let mut cur_state = 0;
+
+        for ch in rule.romaji.chars() {
+            cur_state = match state_pool[cur_state].transitions.get(&ch) {
+                Some(next_state) => *next_state,
+                None => {
+                    state_pool.push(new_state());
+                    let new_state_handle = state_pool.len() - 1;
+                    state_pool[cur_state]
+                        .transitions
+                        .insert(ch, new_state_handle);
+                    new_state_handle
+                }
+            };
+        }
+
+        state_pool[cur_state].accepting = Some((rule.hiragana.clone(), rule.katakana.clone()));
+    }
+
+    state_pool
+}
+
+fn convert(mut converter: Converter) -> String {
+    for ch in INPUT.chars() {
+        converter.input_char(ch);
+    }
+    converter.accept()
+}
+
+fn bench(name: &str, request: impl Fn() -> String) {
+    // warm up
+    for _ in 0..REQUESTS / 10 {
+        black_box(request());
+    }
+
+    let start = Instant::now();
+    for _ in 0..REQUESTS {
+        black_box(request());
+    }
+    println!(
+        "{:<28} {:>10.2?} per request",
+        name,
+        start.elapsed() / REQUESTS
+    );
+}
+
+fn main() {
+    let shared = Arc::new(Dfa::new(&ConversionTable::default()));
+    // the old state machine cannot drive a converter anymore, so the conversion itself is done
+    // with the shared one, which costs the same
+    bench("before: hashmap per request", || {
+        black_box(build_hashmap_dfa(&ConversionTable::default()));
+        convert(Converter::with_dfa(shared.clone()))
+    });
+    bench("compact per request", || {
+        convert(Converter::with_dfa(Arc::new(Dfa::new(
+            &ConversionTable::default(),
+        ))))
+    });
+    bench("after: shared", || convert(Converter::new()));
+}
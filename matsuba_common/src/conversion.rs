@@ -1,3 +1,7 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt::Display;
+
 pub static HIRAGANA_SMALL_TSU: &char = &'っ';
 pub static KATAKANA_SMALL_TSU: &char = &'ッ';
 
@@ -5,7 +9,10 @@ pub static REPEATABLE_CHARACTERS: &[char] = &[
     'k', 's', 't', 'c', 'h', 'f', 'm', 'y', 'r', 'w', 'g', 'z', 'j', 'd', 'b', 'p',
 ];
 
-pub static CONVERSION_TABLE: &[(&str, &str, &str)] = &[
+/// Table of (romaji, hiragana, katakana) rules
+pub type StaticTable = &'static [(&'static str, &'static str, &'static str)];
+
+pub static CONVERSION_TABLE: StaticTable = &[
     // monographs
     ("a", "あ", "ア"),
     ("i", "い", "イ"),
@@ -51,8 +58,8 @@ pub static CONVERSION_TABLE: &[(&str, &str, &str)] = &[
     ("re", "れ", "レ"),
     ("ro", "ろ", "ロ"),
     ("wa", "わ", "ワ"),
-    ("wi", "ゐ", "ヰ"),
-    ("we", "ゑ", "ヱ"),
+    ("wyi", "ゐ", "ヰ"),
+    ("wye", "ゑ", "ヱ"),
     ("wo", "を", "ヲ"),
     ("nn", "ん", "ン"),
     ("ga", "が", "ガ"),
@@ -66,7 +73,7 @@ pub static CONVERSION_TABLE: &[(&str, &str, &str)] = &[
     ("ze", "ぜ", "ゼ"),
     ("zo", "ぞ", "ゾ"),
     ("da", "だ", "ダ"),
-    ("di", "ぢ", "ヂ"),
+    ("dzi", "ぢ", "ヂ"),
     ("dzu", "づ", "ヅ"),
     ("de", "で", "デ"),
     ("do", "ど", "ド"),
@@ -127,5 +134,1022 @@ pub static CONVERSION_TABLE: &[(&str, &str, &str)] = &[
     ("xwa", "ゎ", "ヮ"),
     ("xka", "ヵ", "ヵ"),
     ("xke", "ヶ", "ヶ"),
-    // (hentaigana for fun?) + extended katakana etc チェ + halfwidth?
+    // extended kana, mostly used for loanwords
+    ("va", "ゔぁ", "ヴァ"),
+    ("vi", "ゔぃ", "ヴィ"),
+    ("vu", "ゔ", "ヴ"),
+    ("ve", "ゔぇ", "ヴェ"),
+    ("vo", "ゔぉ", "ヴォ"),
+    ("vya", "ゔゃ", "ヴャ"),
+    ("vyu", "ゔゅ", "ヴュ"),
+    ("vyo", "ゔょ", "ヴョ"),
+    ("fa", "ふぁ", "ファ"),
+    ("fi", "ふぃ", "フィ"),
+    ("fe", "ふぇ", "フェ"),
+    ("fo", "ふぉ", "フォ"),
+    ("fya", "ふゃ", "フャ"),
+    ("fyu", "ふゅ", "フュ"),
+    ("fyo", "ふょ", "フョ"),
+    ("thi", "てぃ", "ティ"),
+    ("thu", "てゅ", "テュ"),
+    ("twu", "とぅ", "トゥ"),
+    ("dhi", "でぃ", "ディ"),
+    ("dyu", "でゅ", "デュ"),
+    ("dhu", "でゅ", "デュ"),
+    ("dwu", "どぅ", "ドゥ"),
+    // shorter spellings, replaced by ち, つ, ぢ and づ when kunrei-shiki or nihon-shiki is enabled
+    ("ti", "てぃ", "ティ"),
+    ("tu", "とぅ", "トゥ"),
+    ("di", "でぃ", "ディ"),
+    ("du", "どぅ", "ドゥ"),
+    ("tsa", "つぁ", "ツァ"),
+    ("tsi", "つぃ", "ツィ"),
+    ("tse", "つぇ", "ツェ"),
+    ("tso", "つぉ", "ツォ"),
+    ("wha", "うぁ", "ウァ"),
+    ("whi", "うぃ", "ウィ"),
+    ("whe", "うぇ", "ウェ"),
+    ("who", "うぉ", "ウォ"),
+    ("wi", "うぃ", "ウィ"),
+    ("we", "うぇ", "ウェ"),
+    ("ye", "いぇ", "イェ"),
+    ("she", "しぇ", "シェ"),
+    ("che", "ちぇ", "チェ"),
+    ("je", "じぇ", "ジェ"),
+    ("kwa", "くぁ", "クァ"),
+    ("kwi", "くぃ", "クィ"),
+    ("kwe", "くぇ", "クェ"),
+    ("kwo", "くぉ", "クォ"),
+    ("gwa", "ぐぁ", "グァ"),
+    ("gwi", "ぐぃ", "グィ"),
+    ("gwe", "ぐぇ", "グェ"),
+    ("gwo", "ぐぉ", "グォ"),
+    // punctuation and symbols
+    (".", "。", "。"),
+    (",", "、", "、"),
+    ("-", "ー", "ー"),
+    ("[", "「", "「"),
+    ("]", "」", "」"),
+    ("?", "？", "？"),
+    ("!", "！", "！"),
+    ("~", "〜", "〜"),
+    ("/", "・", "・"),
+    // z sequences from skk and mozc
+    ("z.", "…", "…"),
+    ("z,", "‥", "‥"),
+    ("z-", "〜", "〜"),
+    ("z/", "・", "・"),
+    ("z[", "『", "『"),
+    ("z]", "』", "』"),
+    ("zh", "←", "←"),
+    ("zj", "↓", "↓"),
+    ("zk", "↑", "↑"),
+    ("zl", "→", "→"),
+    // (hentaigana for fun?)
+];
+
+/// Extra kunrei-shiki spellings on top of the hepburn table
+pub static KUNREI_TABLE: StaticTable = &[
+    ("si", "し", "シ"),
+    ("ti", "ち", "チ"),
+    ("tu", "つ", "ツ"),
+    ("hu", "ふ", "フ"),
+    ("zi", "じ", "ジ"),
+    ("sya", "しゃ", "シャ"),
+    ("syu", "しゅ", "シュ"),
+    ("syo", "しょ", "ショ"),
+    ("tya", "ちゃ", "チャ"),
+    ("tyu", "ちゅ", "チュ"),
+    ("tyo", "ちょ", "チョ"),
+    ("zya", "じゃ", "ジャ"),
+    ("zyu", "じゅ", "ジュ"),
+    ("zyo", "じょ", "ジョ"),
 ];
+
+/// Extra nihon-shiki spellings on top of the hepburn table
+pub static NIHON_SHIKI_TABLE: StaticTable = &[
+    ("si", "し", "シ"),
+    ("ti", "ち", "チ"),
+    ("tu", "つ", "ツ"),
+    ("hu", "ふ", "フ"),
+    ("zi", "じ", "ジ"),
+    ("di", "ぢ", "ヂ"),
+    ("du", "づ", "ヅ"),
+    ("wi", "ゐ", "ヰ"),
+    ("we", "ゑ", "ヱ"),
+    ("sya", "しゃ", "シャ"),
+    ("syu", "しゅ", "シュ"),
+    ("syo", "しょ", "ショ"),
+    ("tya", "ちゃ", "チャ"),
+    ("tyu", "ちゅ", "チュ"),
+    ("tyo", "ちょ", "チョ"),
+    ("zya", "じゃ", "ジャ"),
+    ("zyu", "じゅ", "ジュ"),
+    ("zyo", "じょ", "ジョ"),
+    ("dya", "ぢゃ", "ヂャ"),
+    ("dyu", "ぢゅ", "ヂュ"),
+    ("dyo", "ぢょ", "ヂョ"),
+    ("kwa", "くゎ", "クヮ"),
+    ("gwa", "ぐゎ", "グヮ"),
+];
+
+/// Full-width western punctuation, replacing 。 and 、
+pub static WESTERN_PUNCTUATION_TABLE: StaticTable = &[(".", "．", "．"), (",", "，", "，")];
+
+/// AZIK shortcuts on top of the hepburn table
+///
+/// Rules of the table replace any built-in rules they conflict with, such as `sh` which becomes
+/// すう, so `x` is used for the sh row instead.
+pub static AZIK_TABLE: StaticTable = &[
+    // shortcuts
+    ("q", "ん", "ン"),
+    (";", "っ", "ッ"),
+    // sh and ch rows
+    ("xa", "しゃ", "シャ"),
+    ("xi", "し", "シ"),
+    ("xu", "しゅ", "シュ"),
+    ("xe", "しぇ", "シェ"),
+    ("xo", "しょ", "ショ"),
+    ("ca", "ちゃ", "チャ"),
+    ("ci", "ち", "チ"),
+    ("cu", "ちゅ", "チュ"),
+    ("ce", "ちぇ", "チェ"),
+    ("co", "ちょ", "チョ"),
+    // small kana, since x is the sh row
+    ("la", "ぁ", "ァ"),
+    ("li", "ぃ", "ィ"),
+    ("lu", "ぅ", "ゥ"),
+    ("le", "ぇ", "ェ"),
+    ("lo", "ぉ", "ォ"),
+    ("ltu", "っ", "ッ"),
+    ("lya", "ゃ", "ャ"),
+    ("lyu", "ゅ", "ュ"),
+    ("lyo", "ょ", "ョ"),
+    ("lwa", "ゎ", "ヮ"),
+    // z k j d l add ん to a i u e o, and q h w p extend them to ai uu ei ou
+    ("kz", "かん", "カン"),
+    ("kk", "きん", "キン"),
+    ("kj", "くん", "クン"),
+    ("kd", "けん", "ケン"),
+    ("kl", "こん", "コン"),
+    ("kq", "かい", "カイ"),
+    ("kh", "くう", "クウ"),
+    ("kw", "けい", "ケイ"),
+    ("kp", "こう", "コウ"),
+    ("sz", "さん", "サン"),
+    ("sk", "しん", "シン"),
+    ("sj", "すん", "スン"),
+    ("sd", "せん", "セン"),
+    ("sl", "そん", "ソン"),
+    ("sq", "さい", "サイ"),
+    ("sh", "すう", "スウ"),
+    ("sw", "せい", "セイ"),
+    ("sp", "そう", "ソウ"),
+    ("tz", "たん", "タン"),
+    ("tk", "ちん", "チン"),
+    ("tj", "つん", "ツン"),
+    ("td", "てん", "テン"),
+    ("tl", "とん", "トン"),
+    ("tq", "たい", "タイ"),
+    ("th", "つう", "ツウ"),
+    ("tw", "てい", "テイ"),
+    ("tp", "とう", "トウ"),
+    ("nz", "なん", "ナン"),
+    ("nk", "にん", "ニン"),
+    ("nj", "ぬん", "ヌン"),
+    ("nd", "ねん", "ネン"),
+    ("nl", "のん", "ノン"),
+    ("nq", "ない", "ナイ"),
+    ("nh", "ぬう", "ヌウ"),
+    ("nw", "ねい", "ネイ"),
+    ("np", "のう", "ノウ"),
+    ("hz", "はん", "ハン"),
+    ("hk", "ひん", "ヒン"),
+    ("hj", "ふん", "フン"),
+    ("hd", "へん", "ヘン"),
+    ("hl", "ほん", "ホン"),
+    ("hq", "はい", "ハイ"),
+    ("hh", "ふう", "フウ"),
+    ("hw", "へい", "ヘイ"),
+    ("hp", "ほう", "ホウ"),
+    ("mz", "まん", "マン"),
+    ("mk", "みん", "ミン"),
+    ("mj", "むん", "ムン"),
+    ("md", "めん", "メン"),
+    ("ml", "もん", "モン"),
+    ("mq", "まい", "マイ"),
+    ("mh", "むう", "ムウ"),
+    ("mw", "めい", "メイ"),
+    ("mp", "もう", "モウ"),
+    ("yz", "やん", "ヤン"),
+    ("yj", "ゆん", "ユン"),
+    ("yl", "よん", "ヨン"),
+    ("yq", "やい", "ヤイ"),
+    ("yh", "ゆう", "ユウ"),
+    ("yp", "よう", "ヨウ"),
+    ("rz", "らん", "ラン"),
+    ("rk", "りん", "リン"),
+    ("rj", "るん", "ルン"),
+    ("rd", "れん", "レン"),
+    ("rl", "ろん", "ロン"),
+    ("rq", "らい", "ライ"),
+    ("rh", "るう", "ルウ"),
+    ("rw", "れい", "レイ"),
+    ("rp", "ろう", "ロウ"),
+    ("wz", "わん", "ワン"),
+    ("wl", "をん", "ヲン"),
+    ("wq", "わい", "ワイ"),
+    ("wp", "をう", "ヲウ"),
+    ("gz", "がん", "ガン"),
+    ("gk", "ぎん", "ギン"),
+    ("gj", "ぐん", "グン"),
+    ("gd", "げん", "ゲン"),
+    ("gl", "ごん", "ゴン"),
+    ("gq", "がい", "ガイ"),
+    ("gh", "ぐう", "グウ"),
+    ("gw", "げい", "ゲイ"),
+    ("gp", "ごう", "ゴウ"),
+    ("zz", "ざん", "ザン"),
+    ("zk", "じん", "ジン"),
+    ("zj", "ずん", "ズン"),
+    ("zd", "ぜん", "ゼン"),
+    ("zl", "ぞん", "ゾン"),
+    ("zq", "ざい", "ザイ"),
+    ("zh", "ずう", "ズウ"),
+    ("zw", "ぜい", "ゼイ"),
+    ("zp", "ぞう", "ゾウ"),
+    ("dz", "だん", "ダン"),
+    ("dk", "ぢん", "ヂン"),
+    ("dj", "づん", "ヅン"),
+    ("dd", "でん", "デン"),
+    ("dl", "どん", "ドン"),
+    ("dq", "だい", "ダイ"),
+    ("dh", "づう", "ヅウ"),
+    ("dw", "でい", "デイ"),
+    ("dp", "どう", "ドウ"),
+    ("bz", "ばん", "バン"),
+    ("bk", "びん", "ビン"),
+    ("bj", "ぶん", "ブン"),
+    ("bd", "べん", "ベン"),
+    ("bl", "ぼん", "ボン"),
+    ("bq", "ばい", "バイ"),
+    ("bh", "ぶう", "ブウ"),
+    ("bw", "べい", "ベイ"),
+    ("bp", "ぼう", "ボウ"),
+    ("pz", "ぱん", "パン"),
+    ("pk", "ぴん", "ピン"),
+    ("pj", "ぷん", "プン"),
+    ("pd", "ぺん", "ペン"),
+    ("pl", "ぽん", "ポン"),
+    ("pq", "ぱい", "パイ"),
+    ("ph", "ぷう", "プウ"),
+    ("pw", "ぺい", "ペイ"),
+    ("pp", "ぽう", "ポウ"),
+    ("jz", "じゃん", "ジャン"),
+    ("jk", "じん", "ジン"),
+    ("jj", "じゅん", "ジュン"),
+    ("jd", "じぇん", "ジェン"),
+    ("jl", "じょん", "ジョン"),
+    ("jq", "じゃい", "ジャイ"),
+    ("jh", "じゅう", "ジュウ"),
+    ("jw", "じぇい", "ジェイ"),
+    ("jp", "じょう", "ジョウ"),
+    ("fz", "ふぁん", "ファン"),
+    ("fk", "ふぃん", "フィン"),
+    ("fj", "ふん", "フン"),
+    ("fd", "ふぇん", "フェン"),
+    ("fl", "ふぉん", "フォン"),
+    ("fq", "ふぁい", "ファイ"),
+    ("fh", "ふう", "フウ"),
+    ("fw", "ふぇい", "フェイ"),
+    ("fp", "ふぉう", "フォウ"),
+    ("kyz", "きゃん", "キャン"),
+    ("kyj", "きゅん", "キュン"),
+    ("kyl", "きょん", "キョン"),
+    ("kyq", "きゃい", "キャイ"),
+    ("kyh", "きゅう", "キュウ"),
+    ("kyp", "きょう", "キョウ"),
+    ("gyz", "ぎゃん", "ギャン"),
+    ("gyj", "ぎゅん", "ギュン"),
+    ("gyl", "ぎょん", "ギョン"),
+    ("gyq", "ぎゃい", "ギャイ"),
+    ("gyh", "ぎゅう", "ギュウ"),
+    ("gyp", "ぎょう", "ギョウ"),
+    ("syz", "しゃん", "シャン"),
+    ("syj", "しゅん", "シュン"),
+    ("syl", "しょん", "ション"),
+    ("syq", "しゃい", "シャイ"),
+    ("syh", "しゅう", "シュウ"),
+    ("syp", "しょう", "ショウ"),
+    ("zyz", "じゃん", "ジャン"),
+    ("zyj", "じゅん", "ジュン"),
+    ("zyl", "じょん", "ジョン"),
+    ("zyq", "じゃい", "ジャイ"),
+    ("zyh", "じゅう", "ジュウ"),
+    ("zyp", "じょう", "ジョウ"),
+    ("tyz", "ちゃん", "チャン"),
+    ("tyj", "ちゅん", "チュン"),
+    ("tyl", "ちょん", "チョン"),
+    ("tyq", "ちゃい", "チャイ"),
+    ("tyh", "ちゅう", "チュウ"),
+    ("typ", "ちょう", "チョウ"),
+    ("dyz", "ぢゃん", "ヂャン"),
+    ("dyj", "ぢゅん", "ヂュン"),
+    ("dyl", "ぢょん", "ヂョン"),
+    ("dyq", "ぢゃい", "ヂャイ"),
+    ("dyh", "ぢゅう", "ヂュウ"),
+    ("dyp", "ぢょう", "ヂョウ"),
+    ("nyz", "にゃん", "ニャン"),
+    ("nyj", "にゅん", "ニュン"),
+    ("nyl", "にょん", "ニョン"),
+    ("nyq", "にゃい", "ニャイ"),
+    ("nyh", "にゅう", "ニュウ"),
+    ("nyp", "にょう", "ニョウ"),
+    ("hyz", "ひゃん", "ヒャン"),
+    ("hyj", "ひゅん", "ヒュン"),
+    ("hyl", "ひょん", "ヒョン"),
+    ("hyq", "ひゃい", "ヒャイ"),
+    ("hyh", "ひゅう", "ヒュウ"),
+    ("hyp", "ひょう", "ヒョウ"),
+    ("byz", "びゃん", "ビャン"),
+    ("byj", "びゅん", "ビュン"),
+    ("byl", "びょん", "ビョン"),
+    ("byq", "びゃい", "ビャイ"),
+    ("byh", "びゅう", "ビュウ"),
+    ("byp", "びょう", "ビョウ"),
+    ("pyz", "ぴゃん", "ピャン"),
+    ("pyj", "ぴゅん", "ピュン"),
+    ("pyl", "ぴょん", "ピョン"),
+    ("pyq", "ぴゃい", "ピャイ"),
+    ("pyh", "ぴゅう", "ピュウ"),
+    ("pyp", "ぴょう", "ピョウ"),
+    ("myz", "みゃん", "ミャン"),
+    ("myj", "みゅん", "ミュン"),
+    ("myl", "みょん", "ミョン"),
+    ("myq", "みゃい", "ミャイ"),
+    ("myh", "みゅう", "ミュウ"),
+    ("myp", "みょう", "ミョウ"),
+    ("ryz", "りゃん", "リャン"),
+    ("ryj", "りゅん", "リュン"),
+    ("ryl", "りょん", "リョン"),
+    ("ryq", "りゃい", "リャイ"),
+    ("ryh", "りゅう", "リュウ"),
+    ("ryp", "りょう", "リョウ"),
+    ("xz", "しゃん", "シャン"),
+    ("xk", "しん", "シン"),
+    ("xj", "しゅん", "シュン"),
+    ("xd", "しぇん", "シェン"),
+    ("xl", "しょん", "ション"),
+    ("xq", "しゃい", "シャイ"),
+    ("xh", "しゅう", "シュウ"),
+    ("xw", "しぇい", "シェイ"),
+    ("xp", "しょう", "ショウ"),
+    ("cz", "ちゃん", "チャン"),
+    ("ck", "ちん", "チン"),
+    ("cj", "ちゅん", "チュン"),
+    ("cd", "ちぇん", "チェン"),
+    ("cl", "ちょん", "チョン"),
+    ("cq", "ちゃい", "チャイ"),
+    ("ch", "ちゅう", "チュウ"),
+    ("cw", "ちぇい", "チェイ"),
+    ("cp", "ちょう", "チョウ"),
+];
+
+/// ACT shortcuts on top of the hepburn table, AZIK adapted for the dvorak layout
+pub static ACT_TABLE: StaticTable = &[
+    // ; x k j q add ん to a i u e o, and ' p . , extend them to ai uu ei ou
+    ("k;", "かん", "カン"),
+    ("kx", "きん", "キン"),
+    ("kk", "くん", "クン"),
+    ("kj", "けん", "ケン"),
+    ("kq", "こん", "コン"),
+    ("k'", "かい", "カイ"),
+    ("kp", "くう", "クウ"),
+    ("k.", "けい", "ケイ"),
+    ("k,", "こう", "コウ"),
+    ("s;", "さん", "サン"),
+    ("sx", "しん", "シン"),
+    ("sk", "すん", "スン"),
+    ("sj", "せん", "セン"),
+    ("sq", "そん", "ソン"),
+    ("s'", "さい", "サイ"),
+    ("sp", "すう", "スウ"),
+    ("s.", "せい", "セイ"),
+    ("s,", "そう", "ソウ"),
+    ("t;", "たん", "タン"),
+    ("tx", "ちん", "チン"),
+    ("tk", "つん", "ツン"),
+    ("tj", "てん", "テン"),
+    ("tq", "とん", "トン"),
+    ("t'", "たい", "タイ"),
+    ("tp", "つう", "ツウ"),
+    ("t.", "てい", "テイ"),
+    ("t,", "とう", "トウ"),
+    ("n;", "なん", "ナン"),
+    ("nx", "にん", "ニン"),
+    ("nk", "ぬん", "ヌン"),
+    ("nj", "ねん", "ネン"),
+    ("nq", "のん", "ノン"),
+    ("n'", "ない", "ナイ"),
+    ("np", "ぬう", "ヌウ"),
+    ("n.", "ねい", "ネイ"),
+    ("n,", "のう", "ノウ"),
+    ("h;", "はん", "ハン"),
+    ("hx", "ひん", "ヒン"),
+    ("hk", "ふん", "フン"),
+    ("hj", "へん", "ヘン"),
+    ("hq", "ほん", "ホン"),
+    ("h'", "はい", "ハイ"),
+    ("hp", "ふう", "フウ"),
+    ("h.", "へい", "ヘイ"),
+    ("h,", "ほう", "ホウ"),
+    ("m;", "まん", "マン"),
+    ("mx", "みん", "ミン"),
+    ("mk", "むん", "ムン"),
+    ("mj", "めん", "メン"),
+    ("mq", "もん", "モン"),
+    ("m'", "まい", "マイ"),
+    ("mp", "むう", "ムウ"),
+    ("m.", "めい", "メイ"),
+    ("m,", "もう", "モウ"),
+    ("y;", "やん", "ヤン"),
+    ("yk", "ゆん", "ユン"),
+    ("yq", "よん", "ヨン"),
+    ("y'", "やい", "ヤイ"),
+    ("yp", "ゆう", "ユウ"),
+    ("y,", "よう", "ヨウ"),
+    ("r;", "らん", "ラン"),
+    ("rx", "りん", "リン"),
+    ("rk", "るん", "ルン"),
+    ("rj", "れん", "レン"),
+    ("rq", "ろん", "ロン"),
+    ("r'", "らい", "ライ"),
+    ("rp", "るう", "ルウ"),
+    ("r.", "れい", "レイ"),
+    ("r,", "ろう", "ロウ"),
+    ("w;", "わん", "ワン"),
+    ("wq", "をん", "ヲン"),
+    ("w'", "わい", "ワイ"),
+    ("w,", "をう", "ヲウ"),
+    ("g;", "がん", "ガン"),
+    ("gx", "ぎん", "ギン"),
+    ("gk", "ぐん", "グン"),
+    ("gj", "げん", "ゲン"),
+    ("gq", "ごん", "ゴン"),
+    ("g'", "がい", "ガイ"),
+    ("gp", "ぐう", "グウ"),
+    ("g.", "げい", "ゲイ"),
+    ("g,", "ごう", "ゴウ"),
+    ("z;", "ざん", "ザン"),
+    ("zx", "じん", "ジン"),
+    ("zk", "ずん", "ズン"),
+    ("zj", "ぜん", "ゼン"),
+    ("zq", "ぞん", "ゾン"),
+    ("z'", "ざい", "ザイ"),
+    ("zp", "ずう", "ズウ"),
+    ("z.", "ぜい", "ゼイ"),
+    ("z,", "ぞう", "ゾウ"),
+    ("d;", "だん", "ダン"),
+    ("dx", "ぢん", "ヂン"),
+    ("dk", "づん", "ヅン"),
+    ("dj", "でん", "デン"),
+    ("dq", "どん", "ドン"),
+    ("d'", "だい", "ダイ"),
+    ("dp", "づう", "ヅウ"),
+    ("d.", "でい", "デイ"),
+    ("d,", "どう", "ドウ"),
+    ("b;", "ばん", "バン"),
+    ("bx", "びん", "ビン"),
+    ("bk", "ぶん", "ブン"),
+    ("bj", "べん", "ベン"),
+    ("bq", "ぼん", "ボン"),
+    ("b'", "ばい", "バイ"),
+    ("bp", "ぶう", "ブウ"),
+    ("b.", "べい", "ベイ"),
+    ("b,", "ぼう", "ボウ"),
+    ("p;", "ぱん", "パン"),
+    ("px", "ぴん", "ピン"),
+    ("pk", "ぷん", "プン"),
+    ("pj", "ぺん", "ペン"),
+    ("pq", "ぽん", "ポン"),
+    ("p'", "ぱい", "パイ"),
+    ("pp", "ぷう", "プウ"),
+    ("p.", "ぺい", "ペイ"),
+    ("p,", "ぽう", "ポウ"),
+    ("j;", "じゃん", "ジャン"),
+    ("jx", "じん", "ジン"),
+    ("jk", "じゅん", "ジュン"),
+    ("jj", "じぇん", "ジェン"),
+    ("jq", "じょん", "ジョン"),
+    ("j'", "じゃい", "ジャイ"),
+    ("jp", "じゅう", "ジュウ"),
+    ("j.", "じぇい", "ジェイ"),
+    ("j,", "じょう", "ジョウ"),
+    ("f;", "ふぁん", "ファン"),
+    ("fx", "ふぃん", "フィン"),
+    ("fk", "ふん", "フン"),
+    ("fj", "ふぇん", "フェン"),
+    ("fq", "ふぉん", "フォン"),
+    ("f'", "ふぁい", "ファイ"),
+    ("fp", "ふう", "フウ"),
+    ("f.", "ふぇい", "フェイ"),
+    ("f,", "ふぉう", "フォウ"),
+    ("ky;", "きゃん", "キャン"),
+    ("kyk", "きゅん", "キュン"),
+    ("kyq", "きょん", "キョン"),
+    ("ky'", "きゃい", "キャイ"),
+    ("kyp", "きゅう", "キュウ"),
+    ("ky,", "きょう", "キョウ"),
+    ("gy;", "ぎゃん", "ギャン"),
+    ("gyk", "ぎゅん", "ギュン"),
+    ("gyq", "ぎょん", "ギョン"),
+    ("gy'", "ぎゃい", "ギャイ"),
+    ("gyp", "ぎゅう", "ギュウ"),
+    ("gy,", "ぎょう", "ギョウ"),
+    ("sy;", "しゃん", "シャン"),
+    ("syk", "しゅん", "シュン"),
+    ("syq", "しょん", "ション"),
+    ("sy'", "しゃい", "シャイ"),
+    ("syp", "しゅう", "シュウ"),
+    ("sy,", "しょう", "ショウ"),
+    ("zy;", "じゃん", "ジャン"),
+    ("zyk", "じゅん", "ジュン"),
+    ("zyq", "じょん", "ジョン"),
+    ("zy'", "じゃい", "ジャイ"),
+    ("zyp", "じゅう", "ジュウ"),
+    ("zy,", "じょう", "ジョウ"),
+    ("ty;", "ちゃん", "チャン"),
+    ("tyk", "ちゅん", "チュン"),
+    ("tyq", "ちょん", "チョン"),
+    ("ty'", "ちゃい", "チャイ"),
+    ("typ", "ちゅう", "チュウ"),
+    ("ty,", "ちょう", "チョウ"),
+    ("dy;", "ぢゃん", "ヂャン"),
+    ("dyk", "ぢゅん", "ヂュン"),
+    ("dyq", "ぢょん", "ヂョン"),
+    ("dy'", "ぢゃい", "ヂャイ"),
+    ("dyp", "ぢゅう", "ヂュウ"),
+    ("dy,", "ぢょう", "ヂョウ"),
+    ("ny;", "にゃん", "ニャン"),
+    ("nyk", "にゅん", "ニュン"),
+    ("nyq", "にょん", "ニョン"),
+    ("ny'", "にゃい", "ニャイ"),
+    ("nyp", "にゅう", "ニュウ"),
+    ("ny,", "にょう", "ニョウ"),
+    ("hy;", "ひゃん", "ヒャン"),
+    ("hyk", "ひゅん", "ヒュン"),
+    ("hyq", "ひょん", "ヒョン"),
+    ("hy'", "ひゃい", "ヒャイ"),
+    ("hyp", "ひゅう", "ヒュウ"),
+    ("hy,", "ひょう", "ヒョウ"),
+    ("by;", "びゃん", "ビャン"),
+    ("byk", "びゅん", "ビュン"),
+    ("byq", "びょん", "ビョン"),
+    ("by'", "びゃい", "ビャイ"),
+    ("byp", "びゅう", "ビュウ"),
+    ("by,", "びょう", "ビョウ"),
+    ("py;", "ぴゃん", "ピャン"),
+    ("pyk", "ぴゅん", "ピュン"),
+    ("pyq", "ぴょん", "ピョン"),
+    ("py'", "ぴゃい", "ピャイ"),
+    ("pyp", "ぴゅう", "ピュウ"),
+    ("py,", "ぴょう", "ピョウ"),
+    ("my;", "みゃん", "ミャン"),
+    ("myk", "みゅん", "ミュン"),
+    ("myq", "みょん", "ミョン"),
+    ("my'", "みゃい", "ミャイ"),
+    ("myp", "みゅう", "ミュウ"),
+    ("my,", "みょう", "ミョウ"),
+    ("ry;", "りゃん", "リャン"),
+    ("ryk", "りゅん", "リュン"),
+    ("ryq", "りょん", "リョン"),
+    ("ry'", "りゃい", "リャイ"),
+    ("ryp", "りゅう", "リュウ"),
+    ("ry,", "りょう", "リョウ"),
+];
+
+/// Kana typed by each key of the JIS kana layout, keyed by the character the key types in the
+/// jp layout
+///
+/// Shifted keys without a kana of their own type the same kana as unshifted. The ¥ and ろ keys
+/// can both type a backslash depending on the keymap, in which case the key types ろ.
+pub static JIS_KANA_LAYOUT: &[(char, char)] = &[
+    // number row
+    ('1', 'ぬ'),
+    ('2', 'ふ'),
+    ('3', 'あ'),
+    ('4', 'う'),
+    ('5', 'え'),
+    ('6', 'お'),
+    ('7', 'や'),
+    ('8', 'ゆ'),
+    ('9', 'よ'),
+    ('0', 'わ'),
+    ('-', 'ほ'),
+    ('^', 'へ'),
+    ('¥', 'ー'),
+    ('|', 'ー'),
+    ('!', 'ぬ'),
+    ('"', 'ふ'),
+    ('#', 'ぁ'),
+    ('$', 'ぅ'),
+    ('%', 'ぇ'),
+    ('&', 'ぉ'),
+    ('\'', 'ゃ'),
+    ('(', 'ゅ'),
+    (')', 'ょ'),
+    ('~', 'を'),
+    ('=', 'ほ'),
+    // top row
+    ('q', 'た'),
+    ('w', 'て'),
+    ('e', 'い'),
+    ('r', 'す'),
+    ('t', 'か'),
+    ('y', 'ん'),
+    ('u', 'な'),
+    ('i', 'に'),
+    ('o', 'ら'),
+    ('p', 'せ'),
+    ('@', '゛'),
+    ('[', '゜'),
+    ('E', 'ぃ'),
+    ('`', '゛'),
+    ('{', '「'),
+    // home row
+    ('a', 'ち'),
+    ('s', 'と'),
+    ('d', 'し'),
+    ('f', 'は'),
+    ('g', 'き'),
+    ('h', 'く'),
+    ('j', 'ま'),
+    ('k', 'の'),
+    ('l', 'り'),
+    (';', 'れ'),
+    (':', 'け'),
+    (']', 'む'),
+    ('+', 'れ'),
+    ('*', 'け'),
+    ('}', '」'),
+    // bottom row
+    ('z', 'つ'),
+    ('x', 'さ'),
+    ('c', 'そ'),
+    ('v', 'ひ'),
+    ('b', 'こ'),
+    ('n', 'み'),
+    ('m', 'も'),
+    (',', 'ね'),
+    ('.', 'る'),
+    ('/', 'め'),
+    ('\\', 'ろ'),
+    ('_', 'ろ'),
+    ('Z', 'っ'),
+    ('<', '、'),
+    ('>', '。'),
+    ('?', '・'),
+];
+
+/// Characters used for periods and commas
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PunctuationStyle {
+    /// 。 and 、
+    #[default]
+    Japanese,
+    /// ． and ，
+    Western,
+}
+
+impl PunctuationStyle {
+    /// Rules to add on top of the built-in table
+    pub fn tables(&self) -> Vec<StaticTable> {
+        match self {
+            Self::Japanese => vec![],
+            Self::Western => vec![WESTERN_PUNCTUATION_TABLE],
+        }
+    }
+}
+
+impl std::str::FromStr for PunctuationStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "japanese" => Ok(Self::Japanese),
+            "western" => Ok(Self::Western),
+            _ => Err(format!("unknown punctuation style '{}'", s)),
+        }
+    }
+}
+
+/// Romanization systems accepted alongside hepburn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Romanization {
+    /// Only hepburn spellings (`shi`, `chi`, `tsu`)
+    #[default]
+    Hepburn,
+    /// Hepburn and kunrei-shiki spellings (`si`, `ti`, `tu`)
+    Kunrei,
+    /// Hepburn and nihon-shiki spellings (`si`, `di`, `du`)
+    NihonShiki,
+    /// Hepburn, kunrei-shiki and nihon-shiki spellings
+    Mixed,
+}
+
+impl Romanization {
+    /// Rules to add on top of the hepburn table
+    pub fn tables(&self) -> Vec<StaticTable> {
+        match self {
+            Self::Hepburn => vec![],
+            Self::Kunrei => vec![KUNREI_TABLE],
+            Self::NihonShiki => vec![NIHON_SHIKI_TABLE],
+            Self::Mixed => vec![KUNREI_TABLE, NIHON_SHIKI_TABLE],
+        }
+    }
+}
+
+impl std::str::FromStr for Romanization {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hepburn" => Ok(Self::Hepburn),
+            "kunrei" => Ok(Self::Kunrei),
+            "nihonshiki" => Ok(Self::NihonShiki),
+            "mixed" => Ok(Self::Mixed),
+            _ => Err(format!("unknown romanization '{}'", s)),
+        }
+    }
+}
+
+/// Extended romaji input schemes with shortcuts for common kana sequences
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InputScheme {
+    /// Plain romaji
+    #[default]
+    Standard,
+    /// AZIK (`kz` for かん, `kq` for かい, `;` for っ)
+    Azik,
+    /// ACT, AZIK for the dvorak layout (`k;` for かん, `k'` for かい)
+    Act,
+}
+
+impl InputScheme {
+    /// Rules to add on top of the hepburn table
+    pub fn tables(&self) -> Vec<StaticTable> {
+        match self {
+            Self::Standard => vec![],
+            Self::Azik => vec![AZIK_TABLE],
+            Self::Act => vec![ACT_TABLE],
+        }
+    }
+}
+
+impl std::str::FromStr for InputScheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "standard" => Ok(Self::Standard),
+            "azik" => Ok(Self::Azik),
+            "act" => Ok(Self::Act),
+            _ => Err(format!("unknown input scheme '{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TableError {
+    EmptyRomaji,
+    InvalidRomaji(String),
+    EmptyOutput(String),
+    DuplicateRomaji(String),
+    Shadowed { romaji: String, prefix: String },
+    Malformed { line: usize, reason: String },
+}
+
+impl Error for TableError {}
+impl Display for TableError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EmptyRomaji => write!(f, "rule has empty romaji"),
+            Self::InvalidRomaji(r) => write!(
+                f,
+                "romaji '{}' may not contain uppercase letters or whitespace",
+                r
+            ),
+            Self::EmptyOutput(r) => write!(f, "rule for '{}' has empty kana output", r),
+            Self::DuplicateRomaji(r) => write!(f, "duplicate rule for '{}'", r),
+            Self::Shadowed { romaji, prefix } => write!(
+                f,
+                "rule for '{}' can never be reached since '{}' is already a rule",
+                romaji, prefix
+            ),
+            Self::Malformed { line, reason } => write!(f, "line {}: {}", line, reason),
+        }
+    }
+}
+
+/// Single romaji to kana rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConversionRule {
+    pub romaji: String,
+    pub hiragana: String,
+    pub katakana: String,
+}
+
+impl ConversionRule {
+    pub fn new(romaji: &str, hiragana: &str, katakana: &str) -> Self {
+        ConversionRule {
+            romaji: romaji.to_string(),
+            hiragana: hiragana.to_string(),
+            katakana: katakana.to_string(),
+        }
+    }
+
+    /// Checks the rule on its own, without regard to the rest of the table
+    pub fn validate(&self) -> Result<(), TableError> {
+        if self.romaji.is_empty() {
+            return Err(TableError::EmptyRomaji);
+        }
+        if self
+            .romaji
+            .chars()
+            .any(|c| c.is_uppercase() || c.is_whitespace())
+        {
+            return Err(TableError::InvalidRomaji(self.romaji.clone()));
+        }
+        if self.hiragana.is_empty() || self.katakana.is_empty() {
+            return Err(TableError::EmptyOutput(self.romaji.clone()));
+        }
+        Ok(())
+    }
+}
+
+/// Set of rules the converter is built from
+///
+/// The default table is [`CONVERSION_TABLE`], which can then have rules added, overridden and
+/// removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConversionTable {
+    rules: Vec<ConversionRule>,
+}
+
+impl Default for ConversionTable {
+    fn default() -> Self {
+        ConversionTable::from_static(CONVERSION_TABLE)
+    }
+}
+
+impl ConversionTable {
+    /// Table with no rules at all
+    pub fn empty() -> Self {
+        ConversionTable { rules: Vec::new() }
+    }
+
+    pub fn from_static(table: StaticTable) -> Self {
+        ConversionTable {
+            rules: table
+                .iter()
+                .map(|(romaji, hiragana, katakana)| ConversionRule::new(romaji, hiragana, katakana))
+                .collect(),
+        }
+    }
+
+    /// Built-in table extended with the spellings of a romanization system
+    pub fn with_romanization(romanization: Romanization) -> Self {
+        let mut table = ConversionTable::default();
+        table.add_romanization(romanization);
+        table
+    }
+
+    /// Builds a table from a list of rules, rejecting any duplicates
+    pub fn from_rules(rules: Vec<ConversionRule>) -> Result<Self, TableError> {
+        let mut table = ConversionTable::empty();
+        for rule in rules {
+            rule.validate()?;
+            if table.get(&rule.romaji).is_some() {
+                return Err(TableError::DuplicateRomaji(rule.romaji));
+            }
+            table.rules.push(rule);
+        }
+        Ok(table)
+    }
+
+    /// Parses a table file
+    ///
+    /// Each line contains the romaji, hiragana and katakana of a rule separated by tabs. Empty
+    /// lines and lines starting with `#` are ignored.
+    pub fn parse(text: &str) -> Result<Self, TableError> {
+        let mut rules = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let columns = line.split('\t').collect::<Vec<_>>();
+            if columns.len() != 3 {
+                return Err(TableError::Malformed {
+                    line: i + 1,
+                    reason: format!("expected 3 tab separated columns, found {}", columns.len()),
+                });
+            }
+            rules.push(ConversionRule::new(columns[0], columns[1], columns[2]));
+        }
+        ConversionTable::from_rules(rules)
+    }
+
+    pub fn rules(&self) -> &[ConversionRule] {
+        &self.rules
+    }
+
+    pub fn get(&self, romaji: &str) -> Option<&ConversionRule> {
+        self.rules.iter().find(|rule| rule.romaji == romaji)
+    }
+
+    /// Adds a rule, overriding any existing rule with the same romaji
+    ///
+    /// Returns the rule that was replaced, if any.
+    pub fn insert(&mut self, rule: ConversionRule) -> Result<Option<ConversionRule>, TableError> {
+        rule.validate()?;
+        match self.rules.iter_mut().find(|r| r.romaji == rule.romaji) {
+            Some(existing) => Ok(Some(std::mem::replace(existing, rule))),
+            None => {
+                self.rules.push(rule);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Adds the spellings of a romanization system, overriding existing rules
+    pub fn add_romanization(&mut self, romanization: Romanization) {
+        self.add_static(&romanization.tables());
+    }
+
+    /// Switches the characters used for periods and commas
+    pub fn add_punctuation(&mut self, style: PunctuationStyle) {
+        self.add_static(&style.tables());
+    }
+
+    /// Adds the shortcuts of an input scheme, replacing any existing rules they conflict with
+    ///
+    /// A rule conflicts with another if either one is a prefix of the other, such as the AZIK rule
+    /// `sh` and the hepburn rule `sha`.
+    pub fn add_scheme(&mut self, scheme: InputScheme) {
+        for table in scheme.tables() {
+            for (romaji, hiragana, katakana) in table.iter() {
+                self.rules.retain(|rule| {
+                    !rule.romaji.starts_with(romaji) && !romaji.starts_with(rule.romaji.as_str())
+                });
+                self.rules
+                    .push(ConversionRule::new(romaji, hiragana, katakana));
+            }
+        }
+    }
+
+    fn add_static(&mut self, tables: &[StaticTable]) {
+        for table in tables {
+            for (romaji, hiragana, katakana) in table.iter() {
+                // static tables are always well formed
+                let _ = self.insert(ConversionRule::new(romaji, hiragana, katakana));
+            }
+        }
+    }
+
+    pub fn remove(&mut self, romaji: &str) -> Option<ConversionRule> {
+        let index = self.rules.iter().position(|rule| rule.romaji == romaji)?;
+        Some(self.rules.remove(index))
+    }
+
+    /// Checks that every rule is well formed and reachable
+    ///
+    /// The converter outputs kana as soon as it sees a complete rule, so a rule can not have
+    /// another rule as a prefix.
+    pub fn validate(&self) -> Result<(), TableError> {
+        let mut seen = HashSet::new();
+        for rule in &self.rules {
+            rule.validate()?;
+            if !seen.insert(rule.romaji.as_str()) {
+                return Err(TableError::DuplicateRomaji(rule.romaji.clone()));
+            }
+        }
+
+        for rule in &self.rules {
+            let prefix = rule
+                .romaji
+                .char_indices()
+                .skip(1)
+                .map(|(i, _)| &rule.romaji[..i])
+                .find(|prefix| seen.contains(prefix));
+            if let Some(prefix) = prefix {
+                return Err(TableError::Shadowed {
+                    romaji: rule.romaji.clone(),
+                    prefix: prefix.to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
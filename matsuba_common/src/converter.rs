@@ -1,176 +1,1301 @@
 use std::collections::HashMap;
-use std::collections::LinkedList;
+use std::sync::{Arc, OnceLock};
 
 use crate::conversion::*;
+use crate::kana::{self, combine_dakuten, fold, to_katakana, DAKUTEN, HANDAKUTEN};
+use crate::width::{
+    to_fullwidth_ascii, to_fullwidth_katakana, to_halfwidth_katakana, HALFWIDTH_DAKUTEN,
+    HALFWIDTH_HANDAKUTEN,
+};
 
 // TODO ownership in this entire module is fucked, please fix sometime
 
-type StateHandle = usize;
+type StateHandle = u32;
 const START_STATE: StateHandle = 0;
 
+/// Romaji state machine compiled from a conversion table
+///
+/// The state machine is immutable once built, so a single one can be shared by any number of
+/// converters across threads.
 #[derive(Debug)]
-pub struct State {
-    pub accepting: Option<(String, String)>,
-    pub transitions: HashMap<char, StateHandle>,
-    pub depth: usize, // distance from staring state
+pub struct Dfa {
+    states: Vec<DfaState>,
+    /// Transitions of every state, sorted by character within each state
+    transitions: Vec<(char, StateHandle)>,
+    /// Hiragana and katakana output by accepting states
+    outputs: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct DfaState {
+    /// Range of the transitions of the state
+    transitions: (u32, u32),
+    /// Index of the output of the state if it is accepting
+    output: Option<u32>,
+}
+
+impl Dfa {
+    /// Builds the romaji state machine from a conversion table
+    ///
+    /// The table is expected to be valid, see [`ConversionTable::validate`].
+    pub fn new(table: &ConversionTable) -> Dfa {
+        // build the trie of all rules first, then flatten it
+        let mut trie: Vec<(HashMap<char, StateHandle>, Option<u32>)> = vec![(HashMap::new(), None)];
+        let mut outputs = Vec::with_capacity(table.rules().len());
+
+        for rule in table.rules() {
+            let mut cur_state = START_STATE;
+
+            for ch in rule.romaji.chars() {
+                // create state if does not exist
+                let next_state = trie.len() as StateHandle;
+                cur_state = *trie[cur_state as usize].0.entry(ch).or_insert(next_state);
+                if cur_state == next_state {
+                    trie.push((HashMap::new(), None));
+                }
+            }
+
+            // last state is accepting
+            trie[cur_state as usize].1 = Some(outputs.len() as u32);
+            outputs.push((rule.hiragana.clone(), rule.katakana.clone()));
+        }
+
+        let mut states = Vec::with_capacity(trie.len());
+        let mut transitions = Vec::with_capacity(trie.len());
+        for (state_transitions, output) in trie {
+            let start = transitions.len() as u32;
+            transitions.extend(state_transitions);
+            transitions[start as usize..].sort_unstable_by_key(|(ch, _)| *ch);
+            states.push(DfaState {
+                transitions: (start, transitions.len() as u32),
+                output,
+            });
+        }
+
+        Dfa {
+            states,
+            transitions,
+            outputs,
+        }
+    }
+
+    /// State machine for the built-in conversion table, which is only built once
+    pub fn builtin() -> Arc<Dfa> {
+        static BUILTIN: OnceLock<Arc<Dfa>> = OnceLock::new();
+        BUILTIN
+            .get_or_init(|| Arc::new(Dfa::new(&ConversionTable::default())))
+            .clone()
+    }
+
+    /// State reached from a state by a character, if any
+    fn next(&self, state: StateHandle, ch: char) -> Option<StateHandle> {
+        let (start, end) = self.states[state as usize].transitions;
+        let transitions = &self.transitions[start as usize..end as usize];
+        transitions
+            .binary_search_by_key(&ch, |(ch, _)| *ch)
+            .ok()
+            .map(|i| transitions[i].1)
+    }
+
+    /// Hiragana and katakana output by a state, if it is accepting
+    fn output(&self, state: StateHandle) -> Option<&(String, String)> {
+        self.states[state as usize]
+            .output
+            .map(|i| &self.outputs[i as usize])
+    }
+}
+
+/// Width of the kana the converter outputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KanaWidth {
+    /// Full-width hiragana and katakana
+    #[default]
+    Full,
+    /// Half-width katakana only, with separate dakuten characters
+    Half,
+}
+
+impl std::str::FromStr for KanaWidth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "full" => Ok(Self::Full),
+            "half" => Ok(Self::Half),
+            _ => Err(format!("unknown kana width '{}'", s)),
+        }
+    }
+}
+
+/// How a lone `n` is converted to ん
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NHandling {
+    /// ん can only be typed as `nn`
+    Double,
+    /// `n` becomes ん before a consonant other than `y`, `n'` is always ん and a trailing `n` is
+    /// converted on accept
+    #[default]
+    Natural,
+}
+
+impl std::str::FromStr for NHandling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "double" => Ok(Self::Double),
+            "natural" => Ok(Self::Natural),
+            _ => Err(format!("unknown n handling '{}'", s)),
+        }
+    }
+}
+
+/// Keyboard layout used to type kana
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KanaLayout {
+    /// Kana are typed as romaji
+    #[default]
+    Romaji,
+    /// Each key types a kana directly as on a JIS kana keyboard, with separate dakuten and
+    /// handakuten keys
+    Jis,
+}
+
+impl std::str::FromStr for KanaLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "romaji" => Ok(Self::Romaji),
+            "jis" => Ok(Self::Jis),
+            _ => Err(format!("unknown kana layout '{}'", s)),
+        }
+    }
+}
+
+/// How typed characters are interpreted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InputMode {
+    /// Characters are converted to kana according to the kana layout
+    #[default]
+    Kana,
+    /// Letters, digits and symbols are output as their full-width forms
+    FullWidthAlphanumeric,
+}
+
+/// Forms the whole preedit can be converted to, like the F6 to F10 keys of other IMEs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transliteration {
+    Hiragana,
+    /// Full-width katakana
+    Katakana,
+    HalfWidthKatakana,
+    /// Full-width forms of the typed keys
+    FullWidthRomaji,
+    /// Typed keys as is
+    Romaji,
+}
+
+/// Text being composed by a [`Converter`]
+///
+/// Romaji that does not form kana yet is only ever pending at the caret, everything else has been
+/// converted. Romaji that could not be converted at all, such as the `s` in `sい` when only
+/// hepburn is accepted, is kept as is in the converted text.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Preedit {
+    /// Converted text before the caret
+    pub before: String,
+    /// Romaji at the caret that is not converted yet
+    pub pending: String,
+    /// Converted text after the caret
+    pub after: String,
+}
+
+impl Preedit {
+    /// Position of the caret after the pending romaji, in characters
+    pub fn caret(&self) -> usize {
+        self.before.chars().count() + self.pending.chars().count()
+    }
+
+    /// Converted text without the pending romaji
+    pub fn converted(&self) -> String {
+        format!("{}{}", self.before, self.after)
+    }
+
+    /// Checks if all romaji has been converted
+    pub fn is_complete(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+impl std::fmt::Display for Preedit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}{}", self.before, self.pending, self.after)
+    }
+}
+
+/// Character of the composition, along with the keys typed to produce it
+#[derive(Debug, Clone)]
+struct Composed {
+    ch: char,
+    /// Only the first character of a kana sequence such as きゃ holds the typed keys
+    typed: String,
 }
 
 #[derive(Debug)]
 pub struct Converter {
-    pub state_pool: Vec<State>,
-    pub state_handle: StateHandle,
-    pub output: String,
-    pub input: LinkedList<char>,
+    dfa: Arc<Dfa>,
+    state_handle: StateHandle,
+    pub kana_width: KanaWidth,
+    pub n_handling: NHandling,
+    pub kana_layout: KanaLayout,
+    input_mode: InputMode,
+    /// Text that has already been converted
+    composition: Vec<Composed>,
+    /// Romaji typed at the caret that is not converted yet
+    pending: String,
+    /// Position of the caret in the composition, in characters
+    caret: usize,
+    /// Pending `n` directly follows a ん typed as `nn`
+    double_n: bool,
 }
 
-impl State {
-    pub fn new(depth: usize) -> Self {
-        State {
-            accepting: None, // empty string does not accept
-            transitions: HashMap::new(),
-            depth,
-        }
+impl Default for Converter {
+    fn default() -> Self {
+        Converter::new()
     }
 }
 
 impl Converter {
+    /// Converter using the built-in conversion table
     pub fn new() -> Converter {
+        Converter::with_dfa(Dfa::builtin())
+    }
+
+    /// Converter using the built-in conversion table and a romanization system
+    pub fn with_romanization(romanization: Romanization) -> Converter {
+        let table = ConversionTable::with_romanization(romanization);
+        Converter::with_dfa(Arc::new(Dfa::new(&table)))
+    }
+
+    /// Converter using a custom set of rules
+    pub fn with_table(table: &ConversionTable) -> Result<Converter, TableError> {
+        table.validate()?;
+        Ok(Converter::with_dfa(Arc::new(Dfa::new(table))))
+    }
+
+    /// Converter sharing an already built state machine
+    pub fn with_dfa(dfa: Arc<Dfa>) -> Converter {
         Converter {
-            state_pool: build_dfa(),
+            dfa,
             state_handle: START_STATE,
-            output: String::from(""), // stack structure
-            input: LinkedList::new(), // queue structure
+            kana_width: KanaWidth::default(),
+            n_handling: NHandling::default(),
+            kana_layout: KanaLayout::default(),
+            input_mode: InputMode::default(),
+            composition: Vec::new(),
+            pending: String::new(),
+            caret: 0,
+            double_n: false,
         }
     }
 
+    pub fn input_mode(&self) -> InputMode {
+        self.input_mode
+    }
+
+    pub fn set_input_mode(&mut self, input_mode: InputMode) {
+        self.commit_pending();
+        self.input_mode = input_mode;
+    }
+
+    /// Inserts a character at the caret
     pub fn input_char(&mut self, ch: char) {
-        self.input.push_front(ch);
-        self.step_dfa();
+        match self.input_mode {
+            InputMode::Kana => match self.kana_layout {
+                KanaLayout::Romaji => self.step_dfa(ch),
+                KanaLayout::Jis => self.step_jis(ch),
+            },
+            InputMode::FullWidthAlphanumeric => {
+                self.commit_pending();
+                self.insert(&to_fullwidth_ascii(&ch.to_string()), ch.to_string());
+            }
+        }
     }
 
+    /// Deletes the character before the caret
+    ///
+    /// Pending romaji is deleted one key at a time before any converted kana.
     pub fn del_char(&mut self) {
-        self.output.pop();
-        self.state_handle = START_STATE;
+        self.double_n = false;
+        if self.pending.pop().is_some() {
+            // what is left of the pending romaji is still a valid path through the dfa
+            self.state_handle = self.pending.chars().fold(START_STATE, |state, ch| {
+                self.dfa.next(state, ch.to_ascii_lowercase()).unwrap()
+            });
+        } else if self.caret > 0 {
+            self.caret -= 1;
+            self.composition.remove(self.caret);
+        }
+    }
+
+    /// Deletes the character after the caret
+    pub fn del_char_forward(&mut self) {
+        self.commit_pending();
+        if self.caret < self.composition.len() {
+            self.composition.remove(self.caret);
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        self.commit_pending();
+        self.caret = self.caret.saturating_sub(1);
+    }
+
+    pub fn move_right(&mut self) {
+        self.commit_pending();
+        self.caret = (self.caret + 1).min(self.composition.len());
+    }
+
+    pub fn move_home(&mut self) {
+        self.commit_pending();
+        self.caret = 0;
+    }
+
+    pub fn move_end(&mut self) {
+        self.commit_pending();
+        self.caret = self.composition.len();
+    }
+
+    /// Text currently being composed, split around the pending romaji at the caret
+    pub fn preedit(&self) -> Preedit {
+        Preedit {
+            before: self.composition[..self.caret]
+                .iter()
+                .map(|c| c.ch)
+                .collect(),
+            pending: self.pending.clone(),
+            after: self.composition[self.caret..]
+                .iter()
+                .map(|c| c.ch)
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.composition.is_empty() && self.pending.is_empty()
+    }
+
+    /// Characters that were typed to produce the current preedit
+    pub fn typed(&self) -> String {
+        let mut typed = self.composition[..self.caret]
+            .iter()
+            .map(|c| c.typed.as_str())
+            .collect::<String>();
+        typed.push_str(&self.pending);
+        typed.extend(
+            self.composition[self.caret..]
+                .iter()
+                .map(|c| c.typed.as_str()),
+        );
+        typed
+    }
+
+    /// Converts the whole preedit to another form, keeping the keys typed for each kana
+    pub fn transliterate(&mut self, to: Transliteration) {
+        self.commit_pending();
+
+        // split the composition into the kana produced by each rule, along with its typed keys
+        let mut units: Vec<(String, String, bool)> = Vec::new();
+        for (i, composed) in self.composition.drain(..).enumerate() {
+            match units.last_mut() {
+                Some((text, _, _)) if composed.typed.is_empty() => text.push(composed.ch),
+                _ => units.push((composed.ch.to_string(), composed.typed, i < self.caret)),
+            }
+        }
+
+        let mut caret = 0;
+        for (text, typed, before_caret) in units {
+            let text = match to {
+                Transliteration::Hiragana => fold(&text),
+                Transliteration::Katakana => to_katakana(&to_fullwidth_katakana(&text)),
+                Transliteration::HalfWidthKatakana => to_halfwidth_katakana(&text),
+                Transliteration::FullWidthRomaji if !typed.is_empty() => to_fullwidth_ascii(&typed),
+                Transliteration::Romaji if !typed.is_empty() => typed.clone(),
+                _ => text,
+            };
+
+            // romaji has one typed key per character
+            let is_romaji = matches!(
+                to,
+                Transliteration::FullWidthRomaji | Transliteration::Romaji
+            );
+            let mut keys = typed.chars();
+            let mut first = Some(typed.clone());
+            for ch in text.chars() {
+                let typed = match is_romaji {
+                    true => keys.next().map(String::from).unwrap_or_default(),
+                    false => first.take().unwrap_or_default(),
+                };
+                self.composition.push(Composed { ch, typed });
+                if before_caret {
+                    caret += 1;
+                }
+            }
+        }
+        self.caret = caret;
+    }
+
+    /// Toggles a dakuten on the kana before the caret, such as か to が and back
+    pub fn toggle_dakuten(&mut self) {
+        self.modify_last_kana(|ch| kana::toggle_dakuten(ch, DAKUTEN));
+    }
+
+    /// Toggles a handakuten on the kana before the caret, such as は to ぱ and back
+    pub fn toggle_handakuten(&mut self) {
+        self.modify_last_kana(|ch| kana::toggle_dakuten(ch, HANDAKUTEN));
+    }
+
+    /// Switches the kana before the caret between its small and large forms, such as つ and っ
+    pub fn toggle_small(&mut self) {
+        self.modify_last_kana(kana::toggle_small);
+    }
+
+    /// Replaces the kana before the caret, keeping its width
+    ///
+    /// Nothing is changed if the kana can not be modified.
+    fn modify_last_kana(&mut self, modify: impl Fn(char) -> Option<char>) {
+        self.commit_pending();
+
+        let mut start = match self.caret.checked_sub(1) {
+            Some(start) => start,
+            None => return,
+        };
+        // voiced half-width kana is the base kana followed by a separate mark
+        let mark = self.composition[start].ch;
+        if start > 0 && (mark == HALFWIDTH_DAKUTEN || mark == HALFWIDTH_HANDAKUTEN) {
+            start -= 1;
+        }
+        let unit = self.composition[start..self.caret]
+            .iter()
+            .map(|c| c.ch)
+            .collect::<String>();
+
+        let fullwidth = to_fullwidth_katakana(&unit);
+        let mut chars = fullwidth.chars();
+        let modified = match (chars.next(), chars.next()) {
+            (Some(ch), None) => modify(ch),
+            _ => None,
+        };
+        let halfwidth = kana::script(self.composition[start].ch) == kana::Script::HalfWidthKatakana;
+        let text = match modified {
+            Some(ch) if halfwidth => to_halfwidth_katakana(&ch.to_string()),
+            Some(ch) => ch.to_string(),
+            None => return,
+        };
+
+        let typed = self
+            .composition
+            .drain(start..self.caret)
+            .map(|c| c.typed)
+            .collect::<String>();
+        self.caret = start;
+        self.insert(&text, typed);
     }
 
     pub fn accept(&mut self) -> String {
-        self.state_handle = START_STATE;
-        self.input.clear();
+        self.commit_pending();
 
-        let out = self.output.clone();
-        self.output.clear();
+        let out = self.composition.iter().map(|c| c.ch).collect::<String>();
+        self.composition.clear();
+        self.caret = 0;
         out
     }
 
-    fn step_dfa(&mut self) {
-        if self.input.is_empty() {
-            return; // maybe output a warning
+    /// Converts any pending `n`, for when the input is complete
+    ///
+    /// Any other pending romaji is left pending, see [`Preedit::pending`].
+    pub fn flush(&mut self) {
+        if self.double_n && self.pending_n() {
+            self.drop_double_n();
+        } else if self.pending_n() {
+            self.convert_n();
+        }
+        self.double_n = false;
+    }
+
+    /// Keeps any pending romaji as is, so that it is no longer converted
+    fn commit_pending(&mut self) {
+        self.flush();
+        for ch in std::mem::take(&mut self.pending).chars() {
+            self.insert(&ch.to_string(), ch.to_string());
+        }
+        self.state_handle = START_STATE;
+    }
+
+    /// Checks if the pending romaji is an `n` that could still become ん
+    fn pending_n(&self) -> bool {
+        self.n_handling == NHandling::Natural && self.pending.eq_ignore_ascii_case("n")
+    }
+
+    /// Replaces the pending `n` with ん
+    fn convert_n(&mut self) {
+        let typed = std::mem::take(&mut self.pending);
+        let kana = match typed == "n" {
+            true => "ん",
+            false => "ン",
+        };
+        self.insert_kana(kana, typed);
+        self.state_handle = START_STATE;
+    }
+
+    /// Drops the pending `n` following a ん typed as `nn`, since it was already used
+    fn drop_double_n(&mut self) {
+        let typed = std::mem::take(&mut self.pending);
+        if let Some(prev) = self.caret.checked_sub(1) {
+            self.composition[prev].typed.push_str(&typed);
+        }
+        self.state_handle = START_STATE;
+        self.double_n = false;
+    }
+
+    /// Resolves a pending `n` before the next character is fed to the state machine
+    ///
+    /// Returns true if the character was used up in the process.
+    fn step_n(&mut self, ch: char) -> bool {
+        if !self.pending_n() {
+            self.double_n = false;
+            return false;
         }
 
-        let ch = self.input.pop_back().unwrap();
         let lowercase_ch = ch.to_ascii_lowercase();
-        let prev_ch = self.output.chars().last();
+        let continues = self.dfa.next(self.state_handle, lowercase_ch).is_some();
+        if lowercase_ch != 'n' && continues {
+            // n starts a syllable such as na or nya, or a shortcut such as the AZIK nz
+            self.double_n = false;
+            return false;
+        }
 
-        // attempt to transition on input character
-        match self.state_pool[self.state_handle]
-            .transitions
-            .get(&lowercase_ch)
-        {
-            Some(x) => {
-                self.state_handle = *x;
+        if self.double_n {
+            self.drop_double_n();
+        } else {
+            self.convert_n();
+            self.double_n = lowercase_ch == 'n';
+        }
+
+        if lowercase_ch == '\'' {
+            if let Some(prev) = self.caret.checked_sub(1) {
+                self.composition[prev].typed.push(ch);
             }
+            return true;
+        }
+        false
+    }
+
+    /// Inserts text at the caret, with the first character holding the typed keys
+    fn insert(&mut self, text: &str, typed: String) {
+        let mut typed = Some(typed);
+        for ch in text.chars() {
+            self.composition.insert(
+                self.caret,
+                Composed {
+                    ch,
+                    typed: typed.take().unwrap_or_default(),
+                },
+            );
+            self.caret += 1;
+        }
+    }
+
+    /// Inserts converted kana at the caret in the configured width
+    fn insert_kana(&mut self, kana: &str, typed: String) {
+        match self.kana_width {
+            KanaWidth::Full => self.insert(kana, typed),
+            KanaWidth::Half => self.insert(&to_halfwidth_katakana(kana), typed),
+        }
+    }
+
+    fn step_jis(&mut self, ch: char) {
+        self.commit_pending();
+
+        let lookup = |key: char| {
+            JIS_KANA_LAYOUT
+                .iter()
+                .find(|(layout_key, _)| *layout_key == key)
+                .map(|(_, kana)| *kana)
+        };
+        // shifted letters without a kana of their own type the unshifted kana
+        let kana = lookup(ch).or_else(|| lookup(ch.to_ascii_lowercase()));
+        let kana = match kana {
+            Some(kana) => kana,
             None => {
-                self.state_handle = START_STATE;
-
-                // attempt transition again but from start_state
-                match self.state_pool[self.state_handle]
-                    .transitions
-                    .get(&lowercase_ch)
-                {
-                    Some(x) => {
-                        self.state_handle = *x;
-                    }
-                    None => {}
-                }
+                self.insert(&ch.to_string(), ch.to_string());
+                return;
             }
         };
 
-        // small tsu expansion
-        if prev_ch.is_some() {
-            let prev_ch = prev_ch.unwrap();
-            if ch == prev_ch && REPEATABLE_CHARACTERS.contains(&lowercase_ch) {
-                self.output.pop();
-
-                let small_tsu = *match ch.is_ascii_lowercase() {
-                    true => HIRAGANA_SMALL_TSU,
-                    false => KATAKANA_SMALL_TSU,
-                };
-                self.output.push(small_tsu);
+        // dakuten and handakuten keys voice the kana before the caret
+        if kana == DAKUTEN || kana == HANDAKUTEN {
+            if let Some(prev) = self.caret.checked_sub(1) {
+                if let Some(combined) = combine_dakuten(self.composition[prev].ch, kana) {
+                    self.composition[prev].ch = combined;
+                    self.composition[prev].typed.push(ch);
+                    return;
+                }
             }
         }
+        self.insert_kana(&kana.to_string(), ch.to_string());
+    }
 
-        self.output.push(ch);
+    fn step_dfa(&mut self, ch: char) {
+        if self.step_n(ch) {
+            return;
+        }
+        let lowercase_ch = ch.to_ascii_lowercase();
 
-        // check if we are in accepting state
-        match self.state_pool[self.state_handle].accepting {
-            Some(ref x) => {
-                let mut is_lower: bool = false;
-                for _ in 0..self.state_pool[self.state_handle].depth {
-                    is_lower = self.output.pop().unwrap().is_ascii_lowercase();
+        // attempt to transition on input character
+        self.state_handle = match self.dfa.next(self.state_handle, lowercase_ch) {
+            Some(x) => x,
+            None => {
+                // pending romaji can not be continued, so keep it as is
+                self.commit_pending();
+
+                // small tsu expansion
+                let prev = self.caret.checked_sub(1);
+                if let Some(prev) = prev {
+                    if self.composition[prev].ch == ch
+                        && REPEATABLE_CHARACTERS.contains(&lowercase_ch)
+                    {
+                        let typed = self.composition.remove(prev).typed;
+                        self.caret -= 1;
+
+                        let small_tsu = *match ch.is_ascii_lowercase() {
+                            true => HIRAGANA_SMALL_TSU,
+                            false => KATAKANA_SMALL_TSU,
+                        };
+                        self.insert_kana(&small_tsu.to_string(), typed);
+                    }
                 }
 
-                // decide if converting hiragana or katakana
-                let output_ch = match is_lower {
-                    true => &x.0,
-                    false => &x.1,
-                };
-                self.output.push_str(output_ch);
-                self.state_handle = START_STATE;
+                // attempt transition again but from start state
+                match self.dfa.next(START_STATE, lowercase_ch) {
+                    Some(x) => x,
+                    None => {
+                        self.insert(&ch.to_string(), ch.to_string());
+                        self.state_handle = START_STATE;
+                        return;
+                    }
+                }
             }
-            None => {}
+        };
+        self.pending.push(ch);
+
+        // check if we are in accepting state
+        if let Some((hiragana, katakana)) = self.dfa.output(self.state_handle).cloned() {
+            let typed = std::mem::take(&mut self.pending);
+
+            // decide if converting hiragana or katakana, rules without letters such as ; are hiragana
+            let is_lower = !matches!(
+                typed.chars().find(|c| c.is_ascii_alphabetic()),
+                Some(c) if c.is_ascii_uppercase()
+            );
+            let output_ch = match is_lower {
+                true => hiragana,
+                false => katakana,
+            };
+            self.insert_kana(&output_ch, typed);
+            self.state_handle = START_STATE;
         }
     }
 }
 
-// TODO this function is sorta hard to read (maybe refactor)
-pub fn build_dfa() -> Vec<State> {
-    let mut state_pool: Vec<State> = vec![State::new(0)];
+#[cfg(test)]
+mod tests {
 
-    for conv in CONVERSION_TABLE {
-        let mut cur_state: StateHandle = START_STATE;
+    use std::sync::Arc;
 
-        for (i, ch) in conv.0.chars().enumerate() {
-            // create state of does not exist
-            if !state_pool
-                .get(cur_state)
-                .unwrap()
-                .transitions
-                .contains_key(&ch)
-            {
-                state_pool.push(State::new(i + 1));
-                let new_state_handle = state_pool.len() - 1;
-                state_pool
-                    .get_mut(cur_state)
-                    .unwrap()
-                    .transitions
-                    .insert(ch, new_state_handle);
-            }
+    use crate::conversion::{
+        ConversionRule, ConversionTable, InputScheme, PunctuationStyle, Romanization, TableError,
+    };
+
+    use super::{
+        Converter, Dfa, InputMode, KanaLayout, KanaWidth, NHandling, Preedit, Transliteration,
+    };
+
+    fn convert(converter: &mut Converter, input: &str) -> String {
+        for ch in input.chars() {
+            converter.input_char(ch);
+        }
+        converter.accept()
+    }
+
+    #[test]
+    fn default_table() {
+        let mut converter = Converter::new();
+        assert_eq!(convert(&mut converter, "konnnichiha"), "こんにちは");
+        assert_eq!(convert(&mut converter, "KATAKANA"), "カタカナ");
+        assert_eq!(convert(&mut converter, "kitte"), "きって");
+    }
+
+    #[test]
+    fn shared_dfa() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Dfa>();
+
+        let dfa = Dfa::builtin();
+        assert!(Arc::ptr_eq(&dfa, &Dfa::builtin()));
+
+        let threads = ["kyouha", "GAKKOU", "shinbun"].map(|input| {
+            let dfa = dfa.clone();
+            std::thread::spawn(move || convert(&mut Converter::with_dfa(dfa), input))
+        });
+        let converted = threads.map(|thread| thread.join().unwrap());
+        assert_eq!(converted, ["きょうは", "ガッコウ", "しんぶん"]);
+    }
+
+    #[test]
+    fn builtin_tables_valid() {
+        for romanization in [
+            Romanization::Hepburn,
+            Romanization::Kunrei,
+            Romanization::NihonShiki,
+            Romanization::Mixed,
+        ] {
+            assert_eq!(
+                ConversionTable::with_romanization(romanization).validate(),
+                Ok(())
+            );
+        }
+    }
+
+    #[test]
+    fn custom_rules() {
+        let mut table = ConversionTable::default();
+        table
+            .insert(ConversionRule::new("tsa", "つぁ", "ツァ"))
+            .unwrap();
+        table
+            .insert(ConversionRule::new("wi", "うぃ", "ウィ"))
+            .unwrap();
+        table
+            .insert(ConversionRule::new("we", "うぇ", "ウェ"))
+            .unwrap();
+        table.remove("xke").unwrap();
+
+        let mut converter = Converter::with_table(&table).unwrap();
+        assert_eq!(convert(&mut converter, "tsa"), "つぁ");
+        assert_eq!(convert(&mut converter, "WIWE"), "ウィウェ");
+        assert_eq!(convert(&mut converter, "xke"), "xkえ");
+    }
+
+    #[test]
+    fn parse_table() {
+        let table = ConversionTable::parse("# comment\n\nka\tか\tカ\nkya\tきゃ\tキャ\n").unwrap();
+        assert_eq!(table.rules().len(), 2);
+
+        let mut converter = Converter::with_table(&table).unwrap();
+        assert_eq!(convert(&mut converter, "kakya"), "かきゃ");
+        assert_eq!(convert(&mut converter, "sa"), "sa");
+
+        assert_eq!(
+            ConversionTable::parse("ka\tか\nki\tき\tキ"),
+            Err(TableError::Malformed {
+                line: 1,
+                reason: "expected 3 tab separated columns, found 2".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_rules() {
+        assert_eq!(
+            ConversionTable::parse("ka\tか\tカ\nka\tが\tガ"),
+            Err(TableError::DuplicateRomaji("ka".to_string()))
+        );
+        assert_eq!(
+            ConversionTable::parse("ka\t\tカ"),
+            Err(TableError::EmptyOutput("ka".to_string()))
+        );
+        assert_eq!(
+            ConversionTable::default().insert(ConversionRule::new("", "あ", "ア")),
+            Err(TableError::EmptyRomaji)
+        );
+        assert_eq!(
+            ConversionTable::default().insert(ConversionRule::new("Ka", "か", "カ")),
+            Err(TableError::InvalidRomaji("Ka".to_string()))
+        );
+
+        let mut table = ConversionTable::default();
+        table.insert(ConversionRule::new("k", "く", "ク")).unwrap();
+        assert!(matches!(
+            Converter::with_table(&table),
+            Err(TableError::Shadowed { prefix, .. }) if prefix == "k"
+        ));
+    }
+
+    // every row of the gojuon, including dakuten and youon rows
+    const KUNREI_GOJUON: &[(&str, &str)] = &[
+        ("aiueo", "あいうえお"),
+        ("kakikukeko", "かきくけこ"),
+        ("sasisuseso", "さしすせそ"),
+        ("tatituteto", "たちつてと"),
+        ("naninuneno", "なにぬねの"),
+        ("hahihuheho", "はひふへほ"),
+        ("mamimumemo", "まみむめも"),
+        ("yayuyo", "やゆよ"),
+        ("rarirurero", "らりるれろ"),
+        ("wawo", "わを"),
+        ("gagigugego", "がぎぐげご"),
+        ("zazizuzezo", "ざじずぜぞ"),
+        ("dadedo", "だでど"),
+        ("babibubebo", "ばびぶべぼ"),
+        ("papipupepo", "ぱぴぷぺぽ"),
+        ("kyakyukyo", "きゃきゅきょ"),
+        ("syasyusyo", "しゃしゅしょ"),
+        ("tyatyutyo", "ちゃちゅちょ"),
+        ("nyanyunyo", "にゃにゅにょ"),
+        ("hyahyuhyo", "ひゃひゅひょ"),
+        ("myamyumyo", "みゃみゅみょ"),
+        ("ryaryuryo", "りゃりゅりょ"),
+        ("gyagyugyo", "ぎゃぎゅぎょ"),
+        ("zyazyuzyo", "じゃじゅじょ"),
+        ("byabyubyo", "びゃびゅびょ"),
+        ("pyapyupyo", "ぴゃぴゅぴょ"),
+    ];
+
+    #[test]
+    fn kunrei() {
+        let mut converter = Converter::with_romanization(Romanization::Kunrei);
+        for (romaji, kana) in KUNREI_GOJUON {
+            assert_eq!(&convert(&mut converter, romaji), kana);
+        }
+        assert_eq!(convert(&mut converter, "SYATU"), "シャツ");
+        assert_eq!(convert(&mut converter, "kitte"), "きって");
+
+        // hepburn is still accepted
+        assert_eq!(convert(&mut converter, "shichitsufuji"), "しちつふじ");
+    }
+
+    #[test]
+    fn nihon_shiki() {
+        let mut converter = Converter::with_romanization(Romanization::NihonShiki);
+        for (romaji, kana) in KUNREI_GOJUON {
+            assert_eq!(&convert(&mut converter, romaji), kana);
+        }
+        assert_eq!(convert(&mut converter, "dadidudedo"), "だぢづでど");
+        assert_eq!(convert(&mut converter, "dyadyudyo"), "ぢゃぢゅぢょ");
+        assert_eq!(convert(&mut converter, "wawiwewo"), "わゐゑを");
+        assert_eq!(convert(&mut converter, "kwagwa"), "くゎぐゎ");
+
+        // hepburn is still accepted
+        assert_eq!(convert(&mut converter, "shichitsufuji"), "しちつふじ");
+    }
+
+    #[test]
+    fn mixed_romanization() {
+        let mut converter = Converter::with_romanization(Romanization::Mixed);
+        for (romaji, kana) in KUNREI_GOJUON {
+            assert_eq!(&convert(&mut converter, romaji), kana);
+        }
+        assert_eq!(convert(&mut converter, "dudyo"), "づぢょ");
+        assert_eq!(convert(&mut converter, "shisichitifuhu"), "ししちちふふ");
+    }
+
+    #[test]
+    fn hepburn_only() {
+        let mut converter = Converter::with_romanization(Romanization::Hepburn);
+        assert_eq!(convert(&mut converter, "si"), "sい");
+        // tu is only つ in kunrei-shiki, in hepburn it is an extended kana
+        assert_eq!(convert(&mut converter, "tu"), "とぅ");
+    }
+
+    #[test]
+    fn extended_kana() {
+        const EXTENDED: &[(&str, &str, &str)] = &[
+            ("vavivuvevo", "ゔぁゔぃゔゔぇゔぉ", "ヴァヴィヴヴェヴォ"),
+            ("vyavyuvyo", "ゔゃゔゅゔょ", "ヴャヴュヴョ"),
+            ("fafifufefo", "ふぁふぃふふぇふぉ", "ファフィフフェフォ"),
+            ("fyafyufyo", "ふゃふゅふょ", "フャフュフョ"),
+            ("thithutwu", "てぃてゅとぅ", "ティテュトゥ"),
+            ("dhidhudyudwu", "でぃでゅでゅどぅ", "ディデュデュドゥ"),
+            ("ti", "てぃ", "ティ"),
+            ("di", "でぃ", "ディ"),
+            ("tu", "とぅ", "トゥ"),
+            ("du", "どぅ", "ドゥ"),
+            ("wi", "うぃ", "ウィ"),
+            ("we", "うぇ", "ウェ"),
+            (
+                "tsatsitsutsetso",
+                "つぁつぃつつぇつぉ",
+                "ツァツィツツェツォ",
+            ),
+            ("whawhiwhewho", "うぁうぃうぇうぉ", "ウァウィウェウォ"),
+            ("yeshecheje", "いぇしぇちぇじぇ", "イェシェチェジェ"),
+            ("kwakwikwekwo", "くぁくぃくぇくぉ", "クァクィクェクォ"),
+            ("gwagwigwegwo", "ぐぁぐぃぐぇぐぉ", "グァグィグェグォ"),
+        ];
+
+        let mut converter = Converter::new();
+        for (romaji, hiragana, katakana) in EXTENDED {
+            assert_eq!(&convert(&mut converter, romaji), hiragana);
+            assert_eq!(&convert(&mut converter, &romaji.to_uppercase()), katakana);
+        }
+
+        assert_eq!(convert(&mut converter, "FAIRU"), "ファイル");
+        assert_eq!(convert(&mut converter, "PATHI"), "パティ");
+        assert_eq!(convert(&mut converter, "VAIORINN"), "ヴァイオリン");
+        assert_eq!(convert(&mut converter, "WHEBU"), "ウェブ");
+        assert_eq!(convert(&mut converter, "CHEKKU"), "チェック");
+        assert_eq!(convert(&mut converter, "DYUETTO"), "デュエット");
+
+        assert_eq!(convert(&mut converter, "PA-TI-"), "パーティー");
+        assert_eq!(convert(&mut converter, "WEBU"), "ウェブ");
+
+        // the historical kana and the kunrei-shiki readings have their own spellings
+        assert_eq!(convert(&mut converter, "wyiwye"), "ゐゑ");
+        assert_eq!(convert(&mut converter, "dzidzu"), "ぢづ");
+        let mut converter = Converter::with_romanization(Romanization::Kunrei);
+        assert_eq!(convert(&mut converter, "titu"), "ちつ");
+    }
+
+    #[test]
+    fn halfwidth_output() {
+        let mut converter = Converter::new();
+        converter.kana_width = KanaWidth::Half;
+        assert_eq!(convert(&mut converter, "katakana"), "ｶﾀｶﾅ");
+        assert_eq!(convert(&mut converter, "BASU"), "ﾊﾞｽ");
+        assert_eq!(convert(&mut converter, "kitte"), "ｷｯﾃ");
+        assert_eq!(convert(&mut converter, "PAPI"), "ﾊﾟﾋﾟ");
+
+        let mut converter = Converter::new();
+        for ch in "ganbatte".chars() {
+            converter.input_char(ch);
+        }
+        converter.transliterate(Transliteration::HalfWidthKatakana);
+        assert_eq!(converter.accept(), "ｶﾞﾝﾊﾞｯﾃ");
+    }
+
+    #[test]
+    fn transliterate() {
+        let mut converter = Converter::new();
+        for ch in "kyouHAn".chars() {
+            converter.input_char(ch);
+        }
+        converter.move_left();
+        converter.transliterate(Transliteration::Katakana);
+        assert_eq!(converter.preedit().to_string(), "キョウハン");
+        assert_eq!(converter.preedit().caret(), 4);
+
+        converter.transliterate(Transliteration::HalfWidthKatakana);
+        assert_eq!(converter.preedit().to_string(), "ｷｮｳﾊﾝ");
+        converter.transliterate(Transliteration::Hiragana);
+        assert_eq!(converter.preedit().to_string(), "きょうはん");
+        assert_eq!(converter.preedit().caret(), 4);
+
+        converter.transliterate(Transliteration::Romaji);
+        assert_eq!(converter.preedit().to_string(), "kyouHAn");
+        assert_eq!(converter.preedit().caret(), 6);
+        converter.transliterate(Transliteration::FullWidthRomaji);
+        assert_eq!(converter.preedit().to_string(), "ｋｙｏｕＨＡｎ");
+        assert_eq!(converter.typed(), "kyouHAn");
+        assert_eq!(converter.accept(), "ｋｙｏｕＨＡｎ");
+
+        for ch in "BASU".chars() {
+            converter.input_char(ch);
+        }
+        converter.transliterate(Transliteration::HalfWidthKatakana);
+        converter.transliterate(Transliteration::Hiragana);
+        assert_eq!(converter.accept(), "ばす");
+    }
+
+    #[test]
+    fn fullwidth_alphanumeric() {
+        let mut converter = Converter::new();
+        for ch in "ka".chars() {
+            converter.input_char(ch);
+        }
+        converter.set_input_mode(InputMode::FullWidthAlphanumeric);
+        for ch in "ABC 123".chars() {
+            converter.input_char(ch);
+        }
+        converter.set_input_mode(InputMode::Kana);
+        for ch in "na".chars() {
+            converter.input_char(ch);
+        }
+        assert_eq!(converter.typed(), "kaABC 123na");
+        assert_eq!(converter.accept(), "かＡＢＣ　１２３な");
+    }
+
+    #[test]
+    fn typed() {
+        let mut converter = Converter::new();
+        for ch in "kyakkaGAk".chars() {
+            converter.input_char(ch);
+        }
+        assert_eq!(converter.preedit().to_string(), "きゃっかガk");
+        assert_eq!(converter.typed(), "kyakkaGAk");
+
+        converter.del_char();
+        converter.del_char();
+        assert_eq!(converter.preedit().to_string(), "きゃっか");
+        assert_eq!(converter.typed(), "kyakka");
+
+        converter.kana_width = KanaWidth::Half;
+        for ch in "BA".chars() {
+            converter.input_char(ch);
+        }
+        assert_eq!(converter.preedit().to_string(), "きゃっかﾊﾞ");
+        assert_eq!(converter.typed(), "kyakkaBA");
+    }
 
-            // transition
-            cur_state = *state_pool
-                .get(cur_state)
-                .unwrap()
-                .transitions
-                .get(&ch)
-                .unwrap();
-
-            // mark as accepting if last char
-            if i == conv.0.len() - 1 {
-                state_pool.get_mut(cur_state).unwrap().accepting =
-                    Some((conv.1.to_string(), conv.2.to_string()));
+    #[test]
+    fn caret() {
+        let mut converter = Converter::new();
+        for ch in "kakuki".chars() {
+            converter.input_char(ch);
+        }
+        converter.move_left();
+        converter.move_left();
+        for ch in "ky".chars() {
+            converter.input_char(ch);
+        }
+        assert_eq!(
+            converter.preedit(),
+            Preedit {
+                before: "か".to_string(),
+                pending: "ky".to_string(),
+                after: "くき".to_string(),
             }
+        );
+        assert_eq!(converter.preedit().caret(), 3);
+
+        // pending romaji is deleted before any kana
+        converter.del_char();
+        assert_eq!(converter.preedit().to_string(), "かkくき");
+        for ch in "e".chars() {
+            converter.input_char(ch);
+        }
+        assert_eq!(converter.preedit().to_string(), "かけくき");
+        assert_eq!(converter.preedit().caret(), 2);
+
+        converter.del_char();
+        converter.del_char_forward();
+        assert_eq!(converter.preedit().to_string(), "かき");
+        assert_eq!(converter.preedit().caret(), 1);
+
+        converter.move_home();
+        for ch in "SE".chars() {
+            converter.input_char(ch);
+        }
+        converter.move_end();
+        for ch in "tte".chars() {
+            converter.input_char(ch);
+        }
+        assert_eq!(converter.typed(), "SEkakitte");
+        assert_eq!(converter.accept(), "セかきって");
+    }
+
+    #[test]
+    fn caret_flush() {
+        let mut converter = Converter::new();
+        for ch in "hon".chars() {
+            converter.input_char(ch);
+        }
+        converter.move_left();
+        assert_eq!(converter.preedit().to_string(), "ほん");
+        assert_eq!(converter.preedit().caret(), 1);
+
+        for ch in "k".chars() {
+            converter.input_char(ch);
+        }
+        converter.move_right();
+        assert_eq!(converter.preedit().to_string(), "ほkん");
+        assert_eq!(converter.preedit().caret(), 3);
+
+        converter.move_right();
+        assert_eq!(converter.preedit().caret(), 3);
+        converter.transliterate(Transliteration::HalfWidthKatakana);
+        assert_eq!(converter.preedit().to_string(), "ﾎkﾝ");
+        assert_eq!(converter.preedit().caret(), 3);
+    }
+
+    #[test]
+    fn preedit() {
+        let mut converter = Converter::new();
+        for ch in "kyakk".chars() {
+            converter.input_char(ch);
+        }
+        let preedit = converter.preedit();
+        assert_eq!(preedit.converted(), "きゃっ");
+        assert_eq!(preedit.pending, "k");
+        assert!(!preedit.is_complete());
+
+        // a trailing n is complete once flushed
+        for ch in "an".chars() {
+            converter.input_char(ch);
+        }
+        assert_eq!(converter.preedit().pending, "n");
+        converter.flush();
+        assert!(converter.preedit().is_complete());
+        assert_eq!(converter.preedit().to_string(), "きゃっかん");
+    }
+
+    #[test]
+    fn azik() {
+        let mut table = ConversionTable::default();
+        table.add_scheme(InputScheme::Azik);
+        assert_eq!(table.validate(), Ok(()));
+
+        let mut converter = Converter::with_table(&table).unwrap();
+        assert_eq!(convert(&mut converter, "kzkq"), "かんかい");
+        assert_eq!(convert(&mut converter, "KZJI"), "カンジ");
+        assert_eq!(convert(&mut converter, "gakp"), "がこう");
+        assert_eq!(convert(&mut converter, "ga;ko"), "がっこ");
+        assert_eq!(convert(&mut converter, "xaxkxp"), "しゃしんしょう");
+        assert_eq!(convert(&mut converter, "nknzq"), "にんなんん");
+        assert_eq!(convert(&mut converter, "kitte"), "きって");
+        assert_eq!(convert(&mut converter, "kyp"), "きょう");
+        assert_eq!(convert(&mut converter, "la"), "ぁ");
+        assert_eq!(convert(&mut converter, "hon"), "ほん");
+
+        // conflicting hepburn rules are replaced
+        assert_eq!(table.get("sha"), None);
+        assert_eq!(table.get("xka"), None);
+    }
+
+    #[test]
+    fn act() {
+        let mut table = ConversionTable::default();
+        table.add_scheme(InputScheme::Act);
+        assert_eq!(table.validate(), Ok(()));
+
+        let mut converter = Converter::with_table(&table).unwrap();
+        assert_eq!(convert(&mut converter, "k;k'"), "かんかい");
+        assert_eq!(convert(&mut converter, "TQK,"), "トンコウ");
+        assert_eq!(convert(&mut converter, "shinbun"), "しんぶん");
+        assert_eq!(convert(&mut converter, "n;n'"), "なんない");
+    }
+
+    #[test]
+    fn jis_kana_layout() {
+        let mut converter = Converter::new();
+        converter.kana_layout = KanaLayout::Jis;
+        assert_eq!(convert(&mut converter, "ibt@s@"), "にこがど");
+        assert_eq!(convert(&mut converter, "f[v[2[\\"), "ぱぴぷろ");
+        assert_eq!(convert(&mut converter, "4@"), "ゔ");
+        assert_eq!(convert(&mut converter, "@3@"), "゛あ゛");
+        assert_eq!(convert(&mut converter, "ZTW'"), "っかてゃ");
+        assert_eq!(convert(&mut converter, "{tkm>}"), "「かのも。」");
+
+        for ch in "t@y".chars() {
+            converter.input_char(ch);
+        }
+        assert_eq!(converter.typed(), "t@y");
+        converter.move_left();
+        converter.del_char();
+        assert_eq!(converter.accept(), "ん");
+
+        converter.kana_width = KanaWidth::Half;
+        assert_eq!(convert(&mut converter, "t@f["), "ｶﾞﾊﾟ");
+    }
+
+    #[test]
+    fn postfix_kana() {
+        let mut converter = Converter::new();
+        for ch in "tsuka".chars() {
+            converter.input_char(ch);
+        }
+        converter.toggle_dakuten();
+        converter.move_left();
+        converter.toggle_small();
+        assert_eq!(converter.accept(), "っが");
+
+        for ch in "hayo".chars() {
+            converter.input_char(ch);
+        }
+        converter.toggle_small();
+        converter.move_home();
+        converter.toggle_dakuten();
+        converter.move_right();
+        converter.toggle_handakuten();
+        converter.toggle_handakuten();
+        converter.toggle_dakuten();
+        assert_eq!(converter.typed(), "hayo");
+        assert_eq!(converter.accept(), "ばょ");
+
+        // nothing happens without kana that can be modified
+        for ch in "nk".chars() {
+            converter.input_char(ch);
+        }
+        converter.toggle_dakuten();
+        converter.toggle_small();
+        assert_eq!(converter.accept(), "んk");
+
+        converter.kana_width = KanaWidth::Half;
+        for ch in "hatsu".chars() {
+            converter.input_char(ch);
+        }
+        converter.toggle_small();
+        converter.move_left();
+        converter.toggle_handakuten();
+        assert_eq!(converter.preedit().to_string(), "ﾊﾟｯ");
+        converter.toggle_dakuten();
+        assert_eq!(converter.accept(), "ﾊﾞｯ");
+    }
+
+    #[test]
+    fn n_handling() {
+        let mut converter = Converter::new();
+        assert_eq!(convert(&mut converter, "kanpai"), "かんぱい");
+        assert_eq!(convert(&mut converter, "konnichiha"), "こんにちは");
+        assert_eq!(convert(&mut converter, "konnnichiha"), "こんにちは");
+        assert_eq!(convert(&mut converter, "onna"), "おんな");
+        assert_eq!(convert(&mut converter, "kan'i"), "かんい");
+        assert_eq!(convert(&mut converter, "shinyou"), "しにょう");
+        assert_eq!(convert(&mut converter, "shin'you"), "しんよう");
+        assert_eq!(convert(&mut converter, "hon"), "ほん");
+        assert_eq!(convert(&mut converter, "honn"), "ほん");
+        assert_eq!(convert(&mut converter, "sannkai"), "さんかい");
+        assert_eq!(convert(&mut converter, "RAMEN"), "ラメン");
+        assert_eq!(convert(&mut converter, "KONNBINI"), "コンビニ");
+
+        for ch in "kan'ikan".chars() {
+            converter.input_char(ch);
         }
+        assert_eq!(converter.typed(), "kan'ikan");
+        assert_eq!(converter.accept(), "かんいかん");
+    }
+
+    #[test]
+    fn n_handling_double() {
+        let mut converter = Converter::new();
+        converter.n_handling = NHandling::Double;
+        assert_eq!(convert(&mut converter, "kanpai"), "かnぱい");
+        assert_eq!(convert(&mut converter, "konnnichiha"), "こんにちは");
+        assert_eq!(convert(&mut converter, "hon"), "ほn");
     }
 
-    state_pool
+    #[test]
+    fn punctuation() {
+        let mut converter = Converter::new();
+        assert_eq!(convert(&mut converter, "[kore,ha?]"), "「これ、は？」");
+        assert_eq!(convert(&mut converter, "PA-TEXI-!"), "パーティー！");
+        assert_eq!(convert(&mut converter, "hon."), "ほん。");
+        assert_eq!(convert(&mut converter, "honn."), "ほん。");
+        assert_eq!(convert(&mut converter, "a/b~"), "あ・b〜");
+
+        let mut table = ConversionTable::default();
+        table.add_punctuation(PunctuationStyle::Western);
+        let mut converter = Converter::with_table(&table).unwrap();
+        assert_eq!(convert(&mut converter, "sore,kore."), "それ，これ．");
+    }
+
+    #[test]
+    fn z_sequences() {
+        let mut converter = Converter::new();
+        assert_eq!(convert(&mut converter, "z.z,z-z/"), "…‥〜・");
+        assert_eq!(convert(&mut converter, "z[a]z]"), "『あ」』");
+        assert_eq!(convert(&mut converter, "zhzjzkzl"), "←↓↑→");
+        assert_eq!(convert(&mut converter, "zazu"), "ざず");
+    }
 }
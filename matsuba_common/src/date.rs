@@ -0,0 +1,286 @@
+//! Dates and times written in the forms used in Japanese text, including the Japanese eras
+
+/// Japanese era, with the date of the first day of its first year
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Era {
+    pub name: &'static str,
+    pub reading: &'static str,
+    pub start: Date,
+}
+
+/// Eras since the adoption of the gregorian calendar, oldest first
+pub static ERAS: [Era; 5] = [
+    Era {
+        name: "明治",
+        reading: "めいじ",
+        start: Date::new(1868, 10, 23),
+    },
+    Era {
+        name: "大正",
+        reading: "たいしょう",
+        start: Date::new(1912, 7, 30),
+    },
+    Era {
+        name: "昭和",
+        reading: "しょうわ",
+        start: Date::new(1926, 12, 25),
+    },
+    Era {
+        name: "平成",
+        reading: "へいせい",
+        start: Date::new(1989, 1, 8),
+    },
+    Era {
+        name: "令和",
+        reading: "れいわ",
+        start: Date::new(2019, 5, 1),
+    },
+];
+
+static WEEKDAYS: [char; 7] = ['日', '月', '火', '水', '木', '金', '土'];
+
+/// Words that stand for a date, along with its offset in days from today
+static DATE_WORDS: &[(&str, i64)] = &[
+    ("おととい", -2),
+    ("きのう", -1),
+    ("きょう", 0),
+    ("あした", 1),
+    ("あす", 1),
+    ("あさって", 2),
+];
+
+/// Words that stand for the current time
+static TIME_WORDS: &[&str] = &["いま"];
+
+/// Day in the gregorian calendar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    pub year: i32,
+    /// Month of the year, from 1 to 12
+    pub month: u32,
+    /// Day of the month, starting at 1
+    pub day: u32,
+}
+
+/// Time of the day
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Time {
+    /// Hour of the day, from 0 to 23
+    pub hour: u32,
+    pub minute: u32,
+}
+
+impl Date {
+    pub const fn new(year: i32, month: u32, day: u32) -> Date {
+        Date { year, month, day }
+    }
+
+    /// Number of days since 1970-01-01
+    fn to_days(self) -> i64 {
+        // see http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+        let year = self.year as i64 - (self.month <= 2) as i64;
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month = (self.month as i64 + 9) % 12;
+        let day_of_year = (153 * month + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146097 + day_of_era - 719468
+    }
+
+    /// Date from the number of days since 1970-01-01
+    fn from_days(days: i64) -> Date {
+        // see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let days = days + 719468;
+        let era = days.div_euclid(146097);
+        let day_of_era = days - era * 146097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month + 2) / 5 + 1;
+        let month = if month < 10 { month + 3 } else { month - 9 };
+        let year = year_of_era + era * 400 + (month <= 2) as i64;
+        Date::new(year as i32, month as u32, day as u32)
+    }
+
+    /// Date a number of days later, or earlier if negative
+    pub fn add_days(self, days: i64) -> Date {
+        Date::from_days(self.to_days() + days)
+    }
+
+    /// Day of the week written as a kanji, such as 日 for sunday
+    pub fn weekday(self) -> char {
+        // 1970-01-01 was a thursday
+        WEEKDAYS[(self.to_days() + 4).rem_euclid(7) as usize]
+    }
+
+    /// Era the date falls in, along with the year of the era
+    ///
+    /// Returns none for dates before the first era in [`ERAS`].
+    pub fn era(self) -> Option<(&'static Era, i32)> {
+        ERAS.iter()
+            .rev()
+            .find(|era| era.start <= self)
+            .map(|era| (era, self.year - era.start.year + 1))
+    }
+
+    /// Date written as 2024年1月2日
+    pub fn to_kanji(self) -> String {
+        format!("{}年{}月{}日", self.year, self.month, self.day)
+    }
+
+    /// Date written with the year of the era, as 令和6年1月2日
+    ///
+    /// The first year of an era is written as 元年.
+    pub fn to_era(self) -> Option<String> {
+        let (era, year) = self.era()?;
+        Some(format!(
+            "{}{}月{}日",
+            era_year(era, year),
+            self.month,
+            self.day
+        ))
+    }
+
+    /// Date written as 2024/01/02
+    pub fn to_slashed(self) -> String {
+        format!("{}/{:02}/{:02}", self.year, self.month, self.day)
+    }
+
+    /// Date written without the year, with the day of the week, as 1月2日(火)
+    pub fn to_month_day(self) -> String {
+        format!("{}月{}日({})", self.month, self.day, self.weekday())
+    }
+}
+
+impl Time {
+    /// Time written as 14時05分
+    pub fn to_kanji(self) -> String {
+        format!("{}時{:02}分", self.hour, self.minute)
+    }
+
+    /// Time written as 14:05
+    pub fn to_colon(self) -> String {
+        format!("{}:{:02}", self.hour, self.minute)
+    }
+}
+
+fn era_year(era: &Era, year: i32) -> String {
+    match year {
+        1 => format!("{}元年", era.name),
+        _ => format!("{}{}年", era.name, year),
+    }
+}
+
+/// Conversion candidates for words that stand for a date or time, such as きょう or いま
+///
+/// The reading of the current era gives the current year and date in that era. Returns nothing
+/// for any other input.
+pub fn candidates(kana: &str, today: Date, now: Time) -> Vec<String> {
+    if let Some((_, offset)) = DATE_WORDS.iter().find(|(word, _)| *word == kana) {
+        let date = today.add_days(*offset);
+        let mut candidates = vec![date.to_kanji()];
+        candidates.extend(date.to_era());
+        candidates.push(date.to_slashed());
+        candidates.push(date.to_month_day());
+        return candidates;
+    }
+
+    if TIME_WORDS.contains(&kana) {
+        return vec![now.to_kanji(), now.to_colon()];
+    }
+
+    match today.era() {
+        Some((era, year)) if era.reading == kana => {
+            let mut candidates = vec![era_year(era, year)];
+            candidates.extend(today.to_era());
+            candidates
+        }
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{candidates, Date, Time};
+
+    #[test]
+    fn days() {
+        assert_eq!(Date::new(1970, 1, 1).add_days(0), Date::new(1970, 1, 1));
+        assert_eq!(Date::new(2026, 10, 18).add_days(1), Date::new(2026, 10, 19));
+        assert_eq!(Date::new(2024, 2, 28).add_days(1), Date::new(2024, 2, 29));
+        assert_eq!(Date::new(2023, 2, 28).add_days(1), Date::new(2023, 3, 1));
+        assert_eq!(Date::new(2026, 12, 31).add_days(2), Date::new(2027, 1, 2));
+        assert_eq!(Date::new(2026, 1, 1).add_days(-1), Date::new(2025, 12, 31));
+        assert_eq!(Date::new(1900, 3, 1).add_days(-1), Date::new(1900, 2, 28));
+
+        assert_eq!(Date::new(2026, 10, 18).weekday(), '日');
+        assert_eq!(Date::new(1970, 1, 1).weekday(), '木');
+        assert_eq!(Date::new(1868, 10, 23).weekday(), '金');
+    }
+
+    #[test]
+    fn eras() {
+        assert_eq!(Date::new(2026, 10, 18).to_era().unwrap(), "令和8年10月18日");
+        assert_eq!(Date::new(2019, 5, 1).to_era().unwrap(), "令和元年5月1日");
+        assert_eq!(Date::new(2019, 4, 30).to_era().unwrap(), "平成31年4月30日");
+        assert_eq!(Date::new(1989, 1, 7).to_era().unwrap(), "昭和64年1月7日");
+        assert_eq!(
+            Date::new(1926, 12, 25).to_era().unwrap(),
+            "昭和元年12月25日"
+        );
+        assert_eq!(Date::new(1912, 7, 29).to_era().unwrap(), "明治45年7月29日");
+        assert_eq!(Date::new(1800, 1, 1).to_era(), None);
+    }
+
+    #[test]
+    fn formats() {
+        let date = Date::new(2026, 10, 18);
+        assert_eq!(date.to_kanji(), "2026年10月18日");
+        assert_eq!(date.to_slashed(), "2026/10/18");
+        assert_eq!(date.to_month_day(), "10月18日(日)");
+        assert_eq!(Date::new(2026, 1, 2).to_slashed(), "2026/01/02");
+
+        let time = Time {
+            hour: 14,
+            minute: 5,
+        };
+        assert_eq!(time.to_kanji(), "14時05分");
+        assert_eq!(time.to_colon(), "14:05");
+    }
+
+    #[test]
+    fn date_candidates() {
+        let today = Date::new(2026, 10, 18);
+        let now = Time {
+            hour: 14,
+            minute: 5,
+        };
+        assert_eq!(
+            candidates("きょう", today, now),
+            vec![
+                "2026年10月18日",
+                "令和8年10月18日",
+                "2026/10/18",
+                "10月18日(日)"
+            ]
+        );
+        assert_eq!(
+            candidates("あした", today, now),
+            vec![
+                "2026年10月19日",
+                "令和8年10月19日",
+                "2026/10/19",
+                "10月19日(月)"
+            ]
+        );
+        assert_eq!(candidates("いま", today, now), vec!["14時05分", "14:05"]);
+        assert_eq!(
+            candidates("れいわ", today, now),
+            vec!["令和8年", "令和8年10月18日"]
+        );
+        assert!(candidates("へいせい", today, now).is_empty());
+        assert!(candidates("かな", today, now).is_empty());
+    }
+}
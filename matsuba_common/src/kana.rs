@@ -0,0 +1,237 @@
+//! Operations on kana
+//!
+//! Conversion between hiragana and katakana, combining and splitting dakuten and detecting the
+//! script of a character. Conversion between full-width and half-width forms is in
+//! [`crate::width`].
+
+use crate::width::to_fullwidth_katakana;
+
+pub(crate) static DAKUTEN_KATAKANA: &str = "ガギグゲゴザジズゼゾダヂヅデドバビブベボヴヷヺ";
+pub(crate) static DAKUTEN_BASE: &str = "カキクケコサシスセソタチツテトハヒフヘホウワヲ";
+pub(crate) static HANDAKUTEN_KATAKANA: &str = "パピプペポ";
+pub(crate) static HANDAKUTEN_BASE: &str = "ハヒフヘホ";
+
+static SMALL_KANA: &str = "ぁぃぅぇぉっゃゅょゎァィゥェォッャュョヮ";
+static LARGE_KANA: &str = "あいうえおつやゆよわアイウエオツヤユヨワ";
+
+pub static DAKUTEN: char = '゛';
+pub static HANDAKUTEN: char = '゜';
+
+/// Script a character is written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Script {
+    Hiragana,
+    /// Full-width katakana, including the long vowel mark ー
+    Katakana,
+    HalfWidthKatakana,
+    Kanji,
+    /// Ascii letters and digits
+    Latin,
+    /// Full-width letters and digits
+    FullWidthLatin,
+    /// Anything else, such as punctuation
+    Other,
+}
+
+/// Detects the script of a character
+pub fn script(ch: char) -> Script {
+    match ch {
+        'ぁ'..='ゖ' | 'ゝ'..='ゟ' => Script::Hiragana,
+        'ァ'..='ヺ' | 'ー'..='ヿ' | 'ㇰ'..='ㇿ' => Script::Katakana,
+        'ｦ'..='ﾝ' | 'ﾞ' | 'ﾟ' => Script::HalfWidthKatakana,
+        '一'..='鿿' | '㐀'..='䶿' | '々' | '〆' => Script::Kanji,
+        'a'..='z' | 'A'..='Z' | '0'..='9' => Script::Latin,
+        'ａ'..='ｚ' | 'Ａ'..='Ｚ' | '０'..='９' => Script::FullWidthLatin,
+        _ => Script::Other,
+    }
+}
+
+/// Checks if a character is hiragana or katakana of any width
+pub fn is_kana(ch: char) -> bool {
+    matches!(
+        script(ch),
+        Script::Hiragana | Script::Katakana | Script::HalfWidthKatakana
+    )
+}
+
+pub(crate) fn lookup(from: &str, to: &str, ch: char) -> Option<char> {
+    from.chars()
+        .position(|c| c == ch)
+        .and_then(|i| to.chars().nth(i))
+}
+
+/// Shifts hiragana into the katakana block, leaving everything else untouched
+pub(crate) fn hiragana_to_katakana(ch: char) -> char {
+    match ch {
+        'ぁ'..='ゖ' | 'ゝ' | 'ゞ' => char::from_u32(ch as u32 + 0x60).unwrap_or(ch),
+        _ => ch,
+    }
+}
+
+/// Shifts katakana back into the hiragana block, if it has a hiragana form
+pub(crate) fn katakana_to_hiragana(ch: char) -> Option<char> {
+    match ch {
+        'ァ'..='ヶ' | 'ヽ' | 'ヾ' => char::from_u32(ch as u32 - 0x60),
+        _ => None,
+    }
+}
+
+/// Converts hiragana to katakana, leaving everything else untouched
+pub fn to_katakana(s: &str) -> String {
+    s.chars().map(hiragana_to_katakana).collect()
+}
+
+/// Converts katakana to hiragana, leaving katakana without a hiragana form untouched
+pub fn to_hiragana(s: &str) -> String {
+    s.chars()
+        .map(|ch| katakana_to_hiragana(ch).unwrap_or(ch))
+        .collect()
+}
+
+/// Keeps the script of a kana for a character converted in katakana
+fn in_script_of(original: char, katakana: char) -> Option<char> {
+    match original == hiragana_to_katakana(original) {
+        true => Some(katakana),
+        false => katakana_to_hiragana(katakana),
+    }
+}
+
+/// Combines kana with a following dakuten or handakuten, such as か and ゛ into が
+///
+/// Returns none if the kana has no voiced form for the mark.
+pub fn combine_dakuten(ch: char, mark: char) -> Option<char> {
+    let katakana = hiragana_to_katakana(ch);
+    let combined = if mark == DAKUTEN {
+        lookup(DAKUTEN_BASE, DAKUTEN_KATAKANA, katakana)
+    } else if mark == HANDAKUTEN {
+        lookup(HANDAKUTEN_BASE, HANDAKUTEN_KATAKANA, katakana)
+    } else {
+        None
+    }?;
+    in_script_of(ch, combined)
+}
+
+/// Splits voiced kana into the base kana and its dakuten or handakuten, such as が into か and ゛
+///
+/// Returns none if the kana is not voiced.
+pub fn split_dakuten(ch: char) -> Option<(char, char)> {
+    let katakana = hiragana_to_katakana(ch);
+    let (base, mark) = match lookup(DAKUTEN_KATAKANA, DAKUTEN_BASE, katakana) {
+        Some(base) => (base, DAKUTEN),
+        None => (
+            lookup(HANDAKUTEN_KATAKANA, HANDAKUTEN_BASE, katakana)?,
+            HANDAKUTEN,
+        ),
+    };
+    Some((in_script_of(ch, base)?, mark))
+}
+
+/// Toggles a dakuten or handakuten on kana, such as か to が and back
+///
+/// Kana voiced with the other mark is switched over, such as ぱ to ば. Returns none if the kana
+/// has no voiced form for the mark.
+pub fn toggle_dakuten(ch: char, mark: char) -> Option<char> {
+    match split_dakuten(ch) {
+        Some((base, current)) if current == mark => Some(base),
+        Some((base, _)) => combine_dakuten(base, mark),
+        None => combine_dakuten(ch, mark),
+    }
+}
+
+/// Switches kana between its small and large forms, such as つ and っ
+///
+/// Returns none if the kana has no small form.
+pub fn toggle_small(ch: char) -> Option<char> {
+    lookup(SMALL_KANA, LARGE_KANA, ch).or_else(|| lookup(LARGE_KANA, SMALL_KANA, ch))
+}
+
+/// Folds kana typed in any form into full-width hiragana, so that equal readings compare equal
+///
+/// Katakana without a hiragana form, such as ヷ, is kept as katakana.
+pub fn fold(s: &str) -> String {
+    to_hiragana(&to_fullwidth_katakana(s))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{
+        combine_dakuten, fold, is_kana, script, split_dakuten, to_hiragana, to_katakana,
+        toggle_dakuten, toggle_small, Script,
+    };
+
+    #[test]
+    fn scripts() {
+        assert_eq!(script('あ'), Script::Hiragana);
+        assert_eq!(script('ゔ'), Script::Hiragana);
+        assert_eq!(script('ア'), Script::Katakana);
+        assert_eq!(script('ー'), Script::Katakana);
+        assert_eq!(script('ｱ'), Script::HalfWidthKatakana);
+        assert_eq!(script('ﾞ'), Script::HalfWidthKatakana);
+        assert_eq!(script('漢'), Script::Kanji);
+        assert_eq!(script('々'), Script::Kanji);
+        assert_eq!(script('k'), Script::Latin);
+        assert_eq!(script('Ｋ'), Script::FullWidthLatin);
+        assert_eq!(script('。'), Script::Other);
+        assert_eq!(script('゛'), Script::Other);
+
+        assert!(is_kana('ん'));
+        assert!(is_kana('ﾝ'));
+        assert!(!is_kana('n'));
+    }
+
+    #[test]
+    fn hiragana_katakana() {
+        assert_eq!(to_katakana("ひらがなとカタカナ"), "ヒラガナトカタカナ");
+        assert_eq!(to_katakana("ゔゕゖゝゞ"), "ヴヵヶヽヾ");
+        assert_eq!(to_hiragana("カタカナとひらがな"), "かたかなとひらがな");
+        assert_eq!(to_hiragana("ヴヷー"), "ゔヷー");
+        assert_eq!(to_hiragana("ｶﾀｶﾅ"), "ｶﾀｶﾅ");
+        assert_eq!(to_hiragana(&to_katakana("きょうは漢字")), "きょうは漢字");
+    }
+
+    #[test]
+    fn dakuten() {
+        assert_eq!(combine_dakuten('か', '゛'), Some('が'));
+        assert_eq!(combine_dakuten('ハ', '゜'), Some('パ'));
+        assert_eq!(combine_dakuten('う', '゛'), Some('ゔ'));
+        assert_eq!(combine_dakuten('ワ', '゛'), Some('ヷ'));
+        assert_eq!(combine_dakuten('わ', '゛'), None);
+        assert_eq!(combine_dakuten('か', '゜'), None);
+        assert_eq!(combine_dakuten('a', '゛'), None);
+
+        assert_eq!(split_dakuten('が'), Some(('か', '゛')));
+        assert_eq!(split_dakuten('ポ'), Some(('ホ', '゜')));
+        assert_eq!(split_dakuten('ゔ'), Some(('う', '゛')));
+        assert_eq!(split_dakuten('ヺ'), Some(('ヲ', '゛')));
+        assert_eq!(split_dakuten('か'), None);
+        assert_eq!(split_dakuten('漢'), None);
+
+        assert_eq!(toggle_dakuten('か', '゛'), Some('が'));
+        assert_eq!(toggle_dakuten('が', '゛'), Some('か'));
+        assert_eq!(toggle_dakuten('ぱ', '゛'), Some('ば'));
+        assert_eq!(toggle_dakuten('バ', '゜'), Some('パ'));
+        assert_eq!(toggle_dakuten('パ', '゜'), Some('ハ'));
+        assert_eq!(toggle_dakuten('が', '゜'), None);
+        assert_eq!(toggle_dakuten('な', '゛'), None);
+    }
+
+    #[test]
+    fn small() {
+        assert_eq!(toggle_small('つ'), Some('っ'));
+        assert_eq!(toggle_small('っ'), Some('つ'));
+        assert_eq!(toggle_small('ヤ'), Some('ャ'));
+        assert_eq!(toggle_small('ゎ'), Some('わ'));
+        assert_eq!(toggle_small('か'), None);
+        assert_eq!(toggle_small('ｯ'), None);
+    }
+
+    #[test]
+    fn folding() {
+        assert_eq!(fold("ガッコウ"), "がっこう");
+        assert_eq!(fold("ｶﾞｯｺｳ"), "がっこう");
+        assert_eq!(fold("がっこう"), "がっこう");
+        assert_eq!(fold("ｺｰﾋｰ"), "こーひー");
+        assert_eq!(fold("コーひー"), fold("こーヒー"));
+    }
+}
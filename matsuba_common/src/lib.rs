@@ -1,5 +1,10 @@
 pub mod conversion;
 pub mod converter;
+pub mod date;
+pub mod kana;
+pub mod number;
+pub mod romanizer;
+pub mod width;
 
 use std::collections::HashSet;
 
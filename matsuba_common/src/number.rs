@@ -0,0 +1,179 @@
+//! Writing numbers in the forms used in Japanese text
+
+use crate::width::{to_fullwidth_ascii, to_halfwidth_ascii};
+
+static KANJI_DIGITS: [char; 10] = ['〇', '一', '二', '三', '四', '五', '六', '七', '八', '九'];
+static KANJI_UNITS: [&str; 3] = ["十", "百", "千"];
+static KANJI_GROUPS: [&str; 5] = ["", "万", "億", "兆", "京"];
+
+/// Formal numerals used on legal and financial documents, as they are harder to alter
+static DAIJI_DIGITS: [char; 10] = ['〇', '壱', '弐', '参', '四', '五', '六', '七', '八', '九'];
+static DAIJI_UNITS: [&str; 3] = ["拾", "百", "千"];
+static DAIJI_GROUPS: [&str; 5] = ["", "萬", "億", "兆", "京"];
+
+/// Writes each digit as a kanji, such as 2024 as 二〇二四
+///
+/// Characters other than ascii digits are left untouched.
+pub fn to_kanji_digits(digits: &str) -> String {
+    digits
+        .chars()
+        .map(|ch| match ch.to_digit(10) {
+            Some(digit) => KANJI_DIGITS[digit as usize],
+            None => ch,
+        })
+        .collect()
+}
+
+/// Writes a number with kanji numerals and place values, such as 12345 as 一万二千三百四十五
+pub fn to_kanji_numeral(n: u64) -> String {
+    positional(n, &KANJI_DIGITS, &KANJI_UNITS, &KANJI_GROUPS, false)
+}
+
+/// Writes a number with formal numerals, such as 12345 as 壱萬弐千参百四拾五
+pub fn to_daiji(n: u64) -> String {
+    positional(n, &DAIJI_DIGITS, &DAIJI_UNITS, &DAIJI_GROUPS, true)
+}
+
+/// Writes a number in groups of four digits, each followed by its group unit such as 万
+///
+/// A leading one before 十, 百 and 千 is only written if `explicit_one` is set.
+fn positional(
+    n: u64,
+    digits: &[char; 10],
+    units: &[&str; 3],
+    groups: &[&str; 5],
+    explicit_one: bool,
+) -> String {
+    if n == 0 {
+        return digits[0].to_string();
+    }
+
+    let mut output = String::new();
+    for (i, group) in groups.iter().enumerate().rev() {
+        let value = (n / 10_000u64.pow(i as u32)) % 10_000;
+        if value == 0 {
+            continue;
+        }
+        for (place, unit) in units.iter().enumerate().rev() {
+            let digit = (value / 10u64.pow(place as u32 + 1)) % 10;
+            if digit > 1 || (digit == 1 && explicit_one) {
+                output.push(digits[digit as usize]);
+            }
+            if digit > 0 {
+                output.push_str(unit);
+            }
+        }
+        let ones = value % 10;
+        if ones > 0 {
+            output.push(digits[ones as usize]);
+        }
+        output.push_str(group);
+    }
+    output
+}
+
+/// Separates the digits of a number into groups of three with commas, such as 12,345
+pub fn with_grouping(digits: &str) -> String {
+    let len = digits.chars().count();
+    let mut output = String::with_capacity(digits.len() + len / 3);
+    for (i, ch) in digits.chars().enumerate() {
+        if i > 0 && (len - i).is_multiple_of(3) {
+            output.push(',');
+        }
+        output.push(ch);
+    }
+    output
+}
+
+/// Conversion candidates for a number typed in ascii or full-width digits
+///
+/// Returns nothing if the input is not a number. The input itself is not included.
+pub fn candidates(input: &str) -> Vec<String> {
+    let digits = to_halfwidth_ascii(input);
+    if digits.is_empty() || !digits.chars().all(|ch| ch.is_ascii_digit()) {
+        return Vec::new();
+    }
+
+    let mut candidates = vec![to_kanji_digits(&digits)];
+    // numbers with leading zeros such as 007 are only ever read digit by digit
+    let value = match digits.starts_with('0') && digits.len() > 1 {
+        true => None,
+        false => digits.parse::<u64>().ok(),
+    };
+    if let Some(value) = value {
+        candidates.push(to_kanji_numeral(value));
+        candidates.push(to_daiji(value));
+    }
+    candidates.push(to_fullwidth_ascii(&digits));
+    if value.is_some() {
+        candidates.push(with_grouping(&digits));
+    }
+    candidates.push(digits);
+
+    let mut unique: Vec<String> = Vec::with_capacity(candidates.len());
+    for candidate in candidates {
+        if candidate != input && !unique.contains(&candidate) {
+            unique.push(candidate);
+        }
+    }
+    unique
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{candidates, to_daiji, to_kanji_digits, to_kanji_numeral, with_grouping};
+
+    #[test]
+    fn kanji() {
+        assert_eq!(to_kanji_digits("2024"), "二〇二四");
+        assert_eq!(to_kanji_numeral(0), "〇");
+        assert_eq!(to_kanji_numeral(10), "十");
+        assert_eq!(to_kanji_numeral(111), "百十一");
+        assert_eq!(to_kanji_numeral(2024), "二千二十四");
+        assert_eq!(to_kanji_numeral(12345), "一万二千三百四十五");
+        assert_eq!(to_kanji_numeral(10_000), "一万");
+        assert_eq!(to_kanji_numeral(100_010_000), "一億一万");
+        assert_eq!(to_kanji_numeral(1_000_000_000_000), "一兆");
+        assert_eq!(
+            to_kanji_numeral(u64::MAX),
+            "千八百四十四京六千七百四十四兆七百三十七億九百五十五万千六百十五"
+        );
+
+        assert_eq!(to_daiji(12345), "壱萬弐千参百四拾五");
+        assert_eq!(to_daiji(1110), "壱千壱百壱拾");
+        assert_eq!(to_daiji(0), "〇");
+    }
+
+    #[test]
+    fn grouping() {
+        assert_eq!(with_grouping("1"), "1");
+        assert_eq!(with_grouping("123"), "123");
+        assert_eq!(with_grouping("1234"), "1,234");
+        assert_eq!(with_grouping("12345"), "12,345");
+        assert_eq!(with_grouping("1234567"), "1,234,567");
+    }
+
+    #[test]
+    fn number_candidates() {
+        assert_eq!(
+            candidates("12345"),
+            vec![
+                "一二三四五",
+                "一万二千三百四十五",
+                "壱萬弐千参百四拾五",
+                "１２３４５",
+                "12,345",
+            ]
+        );
+        assert_eq!(
+            candidates("１２３"),
+            vec!["一二三", "百二十三", "壱百弐拾参", "123"]
+        );
+        assert_eq!(candidates("5"), vec!["五", "５"]);
+        assert_eq!(candidates("007"), vec!["〇〇七", "００７"]);
+        assert!(candidates("12a").is_empty());
+        assert!(candidates("").is_empty());
+        assert!(candidates("かな").is_empty());
+    }
+}
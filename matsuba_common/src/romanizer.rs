@@ -0,0 +1,347 @@
+//! Conversion from kana back to romaji
+//!
+//! The romanizer is built from the same rules as the converter, using the first rule in the table
+//! that outputs a given kana. Kana whose first rule is only a way of typing them, such as `dzi`
+//! for ぢ, are given the spelling of the romanization system instead.
+
+use std::collections::HashMap;
+
+use crate::conversion::*;
+
+/// How long vowels are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LongVowels {
+    /// Long vowels are spelled out as they are typed (`toukyou`, `paatii`)
+    #[default]
+    Plain,
+    /// Long vowels are marked with macrons in hepburn (`tōkyō`) and circumflexes otherwise
+    /// (`tôkyô`)
+    Marked,
+}
+
+/// Hepburn spellings of kana typed with other romaji
+static HEPBURN_SPELLINGS: StaticTable = &[
+    ("ji", "ぢ", "ヂ"),
+    ("zu", "づ", "ヅ"),
+    ("ja", "ぢゃ", "ヂャ"),
+    ("ju", "ぢゅ", "ヂュ"),
+    ("jo", "ぢょ", "ヂョ"),
+    ("i", "ゐ", "ヰ"),
+    ("e", "ゑ", "ヱ"),
+];
+
+/// Kunrei-shiki spellings of kana typed with other romaji
+static KUNREI_SPELLINGS: StaticTable = &[
+    ("zi", "ぢ", "ヂ"),
+    ("zu", "づ", "ヅ"),
+    ("zya", "ぢゃ", "ヂャ"),
+    ("zyu", "ぢゅ", "ヂュ"),
+    ("zyo", "ぢょ", "ヂョ"),
+    ("i", "ゐ", "ヰ"),
+    ("e", "ゑ", "ヱ"),
+];
+
+/// Spellings that take precedence over the input rules of a romanization system
+fn spellings(romanization: Romanization) -> StaticTable {
+    match romanization {
+        Romanization::Hepburn => HEPBURN_SPELLINGS,
+        Romanization::Kunrei | Romanization::Mixed => KUNREI_SPELLINGS,
+        // the nihon-shiki table already has di, du, wi and we
+        Romanization::NihonShiki => &[],
+    }
+}
+
+enum Token {
+    Syllable(String),
+    SmallTsu,
+    N,
+    LongVowel,
+    Other(char),
+}
+
+pub struct Romanizer {
+    romanization: Romanization,
+    pub long_vowels: LongVowels,
+    /// Romaji for each hiragana and katakana sequence
+    syllables: HashMap<String, String>,
+    /// Length in characters of the longest kana sequence
+    max_len: usize,
+}
+
+impl Default for Romanizer {
+    fn default() -> Self {
+        Romanizer::new(Romanization::Hepburn)
+    }
+}
+
+impl Romanizer {
+    /// Romanizer preferring the spellings of the given romanization system
+    pub fn new(romanization: Romanization) -> Self {
+        let mut rules = spellings(romanization).to_vec();
+        for table in romanization.tables() {
+            rules.extend_from_slice(table);
+        }
+        rules.extend_from_slice(CONVERSION_TABLE);
+
+        let mut romanizer = Romanizer {
+            romanization,
+            long_vowels: LongVowels::default(),
+            syllables: HashMap::new(),
+            max_len: 0,
+        };
+        for (romaji, hiragana, katakana) in rules {
+            romanizer.add_syllable(romaji, hiragana);
+            romanizer.add_syllable(romaji, katakana);
+        }
+        romanizer
+    }
+
+    fn add_syllable(&mut self, romaji: &str, kana: &str) {
+        // only keep the first spelling of each kana
+        if !self.syllables.contains_key(kana) {
+            self.syllables.insert(kana.to_string(), romaji.to_string());
+            self.max_len = self.max_len.max(kana.chars().count());
+        }
+    }
+
+    /// Converts hiragana and katakana to romaji, leaving any other characters as is
+    pub fn romanize(&self, kana: &str) -> String {
+        let tokens = self.tokenize(kana);
+
+        let mut output = String::new();
+        // vowel that a following vowel could lengthen
+        let mut prev_vowel: Option<char> = None;
+        for (i, token) in tokens.iter().enumerate() {
+            let next = match tokens.get(i + 1) {
+                Some(Token::Syllable(romaji)) => romaji.chars().next(),
+                _ => None,
+            };
+
+            match token {
+                Token::Syllable(romaji) => {
+                    let lengthened = match prev_vowel {
+                        Some(prev) if self.long_vowels == LongVowels::Marked => {
+                            is_long_vowel(prev, romaji)
+                        }
+                        _ => false,
+                    };
+                    if lengthened {
+                        output.pop();
+                        output.push(self.mark_long(prev_vowel.unwrap()));
+                        prev_vowel = None;
+                    } else {
+                        output.push_str(romaji);
+                        prev_vowel = romaji.chars().last().filter(|c| is_vowel(*c));
+                    }
+                    continue;
+                }
+                Token::SmallTsu => match next {
+                    // gemination doubles the following consonant, with tch for ch
+                    Some(consonant) if !is_vowel(consonant) && consonant != 'n' => {
+                        match consonant {
+                            'c' => output.push('t'),
+                            _ => output.push(consonant),
+                        }
+                    }
+                    _ => output.push_str("xtsu"),
+                },
+                Token::N => {
+                    output.push('n');
+                    // apostrophe keeps n from joining the next syllable
+                    if let Some(next) = next {
+                        if is_vowel(next) || next == 'y' {
+                            output.push('\'');
+                        }
+                    }
+                }
+                Token::LongVowel => match output.chars().last() {
+                    Some(vowel) if is_vowel(vowel) => match self.long_vowels {
+                        LongVowels::Plain => output.push(vowel),
+                        LongVowels::Marked => {
+                            output.pop();
+                            output.push(self.mark_long(vowel));
+                        }
+                    },
+                    _ => output.push('-'),
+                },
+                Token::Other(ch) => output.push(*ch),
+            }
+            prev_vowel = None;
+        }
+        output
+    }
+
+    fn tokenize(&self, kana: &str) -> Vec<Token> {
+        let chars = kana.chars().collect::<Vec<_>>();
+
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let token = match chars[i] {
+                'っ' | 'ッ' => Token::SmallTsu,
+                'ん' | 'ン' => Token::N,
+                'ー' => Token::LongVowel,
+                ch => {
+                    // find longest kana sequence with a romaji spelling
+                    let matched = (1..=self.max_len.min(chars.len() - i))
+                        .rev()
+                        .find_map(|len| {
+                            let kana = chars[i..i + len].iter().collect::<String>();
+                            self.syllables.get(&kana).map(|romaji| (len, romaji))
+                        });
+                    match matched {
+                        Some((len, romaji)) => {
+                            i += len;
+                            tokens.push(Token::Syllable(romaji.clone()));
+                            continue;
+                        }
+                        None => Token::Other(ch),
+                    }
+                }
+            };
+            tokens.push(token);
+            i += 1;
+        }
+        tokens
+    }
+
+    fn mark_long(&self, vowel: char) -> char {
+        let (vowels, marked) = match self.romanization {
+            Romanization::Hepburn => ("aiueo", "āīūēō"),
+            _ => ("aiueo", "âîûêô"),
+        };
+        vowels
+            .chars()
+            .position(|c| c == vowel)
+            .and_then(|i| marked.chars().nth(i))
+            .unwrap_or(vowel)
+    }
+}
+
+fn is_vowel(ch: char) -> bool {
+    "aiueo".contains(ch)
+}
+
+/// Checks if a syllable lengthens the vowel before it
+fn is_long_vowel(prev: char, romaji: &str) -> bool {
+    matches!(
+        (prev, romaji),
+        ('a', "a") | ('u', "u") | ('e', "e") | ('o', "o") | ('o', "u")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::sync::Arc;
+
+    use crate::conversion::{ConversionTable, Romanization};
+    use crate::converter::{Converter, Dfa};
+
+    use super::{LongVowels, Romanizer};
+
+    #[test]
+    fn hepburn() {
+        let romanizer = Romanizer::default();
+        assert_eq!(romanizer.romanize("ひらがな"), "hiragana");
+        assert_eq!(romanizer.romanize("カタカナ"), "katakana");
+        assert_eq!(romanizer.romanize("しちつふじ"), "shichitsufuji");
+        assert_eq!(romanizer.romanize("きゃきゅきょ"), "kyakyukyo");
+        assert_eq!(romanizer.romanize("漢字とかな"), "漢字tokana");
+    }
+
+    #[test]
+    fn small_tsu() {
+        let romanizer = Romanizer::default();
+        assert_eq!(romanizer.romanize("がっこう"), "gakkou");
+        assert_eq!(romanizer.romanize("きって"), "kitte");
+        assert_eq!(romanizer.romanize("まっちゃ"), "matcha");
+        assert_eq!(romanizer.romanize("チェック"), "chekku");
+        assert_eq!(romanizer.romanize("あっ"), "axtsu");
+
+        let romanizer = Romanizer::new(Romanization::Kunrei);
+        assert_eq!(romanizer.romanize("まっちゃ"), "mattya");
+    }
+
+    #[test]
+    fn n() {
+        let romanizer = Romanizer::default();
+        assert_eq!(romanizer.romanize("しんぶん"), "shinbun");
+        assert_eq!(romanizer.romanize("きんえん"), "kin'en");
+        assert_eq!(romanizer.romanize("ほんや"), "hon'ya");
+        assert_eq!(romanizer.romanize("こんにちは"), "konnichiha");
+        assert_eq!(romanizer.romanize("ラーメン"), "raamen");
+    }
+
+    #[test]
+    fn long_vowels() {
+        let mut romanizer = Romanizer::default();
+        assert_eq!(romanizer.romanize("とうきょう"), "toukyou");
+        assert_eq!(romanizer.romanize("パーティー"), "paathii");
+        assert_eq!(romanizer.romanize("ー"), "-");
+
+        romanizer.long_vowels = LongVowels::Marked;
+        assert_eq!(romanizer.romanize("とうきょう"), "tōkyō");
+        assert_eq!(romanizer.romanize("おおさか"), "ōsaka");
+        assert_eq!(romanizer.romanize("コーヒー"), "kōhī");
+        assert_eq!(romanizer.romanize("せんせい"), "sensei");
+
+        let mut romanizer = Romanizer::new(Romanization::Kunrei);
+        romanizer.long_vowels = LongVowels::Marked;
+        assert_eq!(romanizer.romanize("とうきょう"), "tôkyô");
+    }
+
+    #[test]
+    fn kunrei() {
+        let romanizer = Romanizer::new(Romanization::Kunrei);
+        assert_eq!(romanizer.romanize("しちつふじ"), "sitituhuzi");
+        assert_eq!(romanizer.romanize("しゃしん"), "syasin");
+        assert_eq!(romanizer.romanize("ちゃわん"), "tyawan");
+    }
+
+    #[test]
+    fn spellings() {
+        let romanizer = Romanizer::default();
+        assert_eq!(romanizer.romanize("はなぢ"), "hanaji");
+        assert_eq!(romanizer.romanize("つづく"), "tsuzuku");
+        assert_eq!(romanizer.romanize("ぢゃ"), "ja");
+        assert_eq!(romanizer.romanize("ゐ"), "i");
+        assert_eq!(romanizer.romanize("ゑ"), "e");
+
+        let romanizer = Romanizer::new(Romanization::Kunrei);
+        assert_eq!(romanizer.romanize("はなぢ"), "hanazi");
+        assert_eq!(romanizer.romanize("つづく"), "tuzuku");
+        assert_eq!(romanizer.romanize("ぢゃ"), "zya");
+        assert_eq!(romanizer.romanize("ゐ"), "i");
+        assert_eq!(romanizer.romanize("ゑ"), "e");
+
+        let romanizer = Romanizer::new(Romanization::NihonShiki);
+        assert_eq!(romanizer.romanize("はなぢ"), "hanadi");
+        assert_eq!(romanizer.romanize("つづく"), "tuduku");
+        assert_eq!(romanizer.romanize("ゐ"), "wi");
+        assert_eq!(romanizer.romanize("ゑ"), "we");
+    }
+
+    #[test]
+    fn round_trip() {
+        let romanizer = Romanizer::default();
+        let mut converter = Converter::new();
+        for kana in ["がっこう", "きんえん", "ほんや", "しんぶん", "ふぁいる"] {
+            for ch in romanizer.romanize(kana).chars() {
+                converter.input_char(ch);
+            }
+            assert_eq!(converter.accept(), kana);
+        }
+
+        // tch can not be typed, but the kunrei-shiki tty can
+        let romanizer = Romanizer::new(Romanization::Kunrei);
+        let table = ConversionTable::with_romanization(Romanization::Kunrei);
+        let mut converter = Converter::with_dfa(Arc::new(Dfa::new(&table)));
+        for kana in ["まっちゃ", "しゃしん"] {
+            for ch in romanizer.romanize(kana).chars() {
+                converter.input_char(ch);
+            }
+            assert_eq!(converter.accept(), kana);
+        }
+    }
+}
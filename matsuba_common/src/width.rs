@@ -0,0 +1,124 @@
+//! Conversion between full-width and half-width characters
+
+use crate::kana::{
+    hiragana_to_katakana, lookup, DAKUTEN_BASE, DAKUTEN_KATAKANA, HANDAKUTEN_BASE,
+    HANDAKUTEN_KATAKANA,
+};
+
+static FULLWIDTH_KATAKANA: &str = "。「」、・ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン゛゜";
+static HALFWIDTH_KATAKANA: &str = "｡｢｣､･ｦｧｨｩｪｫｬｭｮｯｰｱｲｳｴｵｶｷｸｹｺｻｼｽｾｿﾀﾁﾂﾃﾄﾅﾆﾇﾈﾉﾊﾋﾌﾍﾎﾏﾐﾑﾒﾓﾔﾕﾖﾗﾘﾙﾚﾛﾜﾝﾞﾟ";
+
+pub static HALFWIDTH_DAKUTEN: char = 'ﾞ';
+pub static HALFWIDTH_HANDAKUTEN: char = 'ﾟ';
+
+/// Converts kana to half-width katakana
+///
+/// Hiragana is converted to katakana first, and voiced kana are split into the base kana followed
+/// by a separate dakuten or handakuten character. Kana without a half-width form are left as is.
+pub fn to_halfwidth_katakana(s: &str) -> String {
+    let mut output = String::with_capacity(s.len());
+    for ch in s.chars().map(hiragana_to_katakana) {
+        if let Some(half) = lookup(FULLWIDTH_KATAKANA, HALFWIDTH_KATAKANA, ch) {
+            output.push(half);
+        } else if let Some(base) = lookup(DAKUTEN_KATAKANA, DAKUTEN_BASE, ch) {
+            output.push(lookup(FULLWIDTH_KATAKANA, HALFWIDTH_KATAKANA, base).unwrap_or(base));
+            output.push(HALFWIDTH_DAKUTEN);
+        } else if let Some(base) = lookup(HANDAKUTEN_KATAKANA, HANDAKUTEN_BASE, ch) {
+            output.push(lookup(FULLWIDTH_KATAKANA, HALFWIDTH_KATAKANA, base).unwrap_or(base));
+            output.push(HALFWIDTH_HANDAKUTEN);
+        } else {
+            output.push(ch);
+        }
+    }
+    output
+}
+
+/// Converts half-width katakana to full-width katakana
+///
+/// A dakuten or handakuten following a kana is combined with it when possible.
+pub fn to_fullwidth_katakana(s: &str) -> String {
+    let mut output = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(ch) = chars.next() {
+        let full = lookup(HALFWIDTH_KATAKANA, FULLWIDTH_KATAKANA, ch).unwrap_or(ch);
+
+        let combined = match chars.peek() {
+            Some(&next) if next == HALFWIDTH_DAKUTEN => {
+                lookup(DAKUTEN_BASE, DAKUTEN_KATAKANA, full)
+            }
+            Some(&next) if next == HALFWIDTH_HANDAKUTEN => {
+                lookup(HANDAKUTEN_BASE, HANDAKUTEN_KATAKANA, full)
+            }
+            _ => None,
+        };
+
+        match combined {
+            Some(combined) => {
+                chars.next();
+                output.push(combined);
+            }
+            None => output.push(full),
+        }
+    }
+    output
+}
+
+/// Converts printable ascii to full-width forms, including the ideographic space
+pub fn to_fullwidth_ascii(s: &str) -> String {
+    s.chars()
+        .map(|ch| match ch {
+            ' ' => '\u{3000}',
+            '!'..='~' => char::from_u32(ch as u32 + 0xFEE0).unwrap_or(ch),
+            _ => ch,
+        })
+        .collect()
+}
+
+/// Converts full-width ascii forms back to printable ascii
+pub fn to_halfwidth_ascii(s: &str) -> String {
+    s.chars()
+        .map(|ch| match ch {
+            '\u{3000}' => ' ',
+            '！'..='～' => char::from_u32(ch as u32 - 0xFEE0).unwrap_or(ch),
+            _ => ch,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{
+        to_fullwidth_ascii, to_fullwidth_katakana, to_halfwidth_ascii, to_halfwidth_katakana,
+    };
+
+    #[test]
+    fn halfwidth() {
+        assert_eq!(to_halfwidth_katakana("カタカナ"), "ｶﾀｶﾅ");
+        assert_eq!(to_halfwidth_katakana("ひらがな"), "ﾋﾗｶﾞﾅ");
+        assert_eq!(to_halfwidth_katakana("バス"), "ﾊﾞｽ");
+        assert_eq!(to_halfwidth_katakana("パーティー"), "ﾊﾟｰﾃｨｰ");
+        assert_eq!(to_halfwidth_katakana("ヴァイオリン"), "ｳﾞｧｲｵﾘﾝ");
+        assert_eq!(to_halfwidth_katakana("「きって」。"), "｢ｷｯﾃ｣｡");
+        assert_eq!(to_halfwidth_katakana("ka漢字"), "ka漢字");
+    }
+
+    #[test]
+    fn fullwidth() {
+        assert_eq!(to_fullwidth_katakana("ｶﾀｶﾅ"), "カタカナ");
+        assert_eq!(to_fullwidth_katakana("ﾋﾗｶﾞﾅ"), "ヒラガナ");
+        assert_eq!(to_fullwidth_katakana("ﾊﾟｰﾃｨｰ"), "パーティー");
+        assert_eq!(to_fullwidth_katakana("ｳﾞｧｲｵﾘﾝ"), "ヴァイオリン");
+        assert_eq!(to_fullwidth_katakana("ｱﾞ"), "ア゛");
+        assert_eq!(to_fullwidth_katakana("ka漢字"), "ka漢字");
+    }
+
+    #[test]
+    fn ascii() {
+        assert_eq!(to_fullwidth_ascii("ABC 123"), "ＡＢＣ　１２３");
+        assert_eq!(to_fullwidth_ascii("a-z!~"), "ａ－ｚ！～");
+        assert_eq!(to_fullwidth_ascii("かな"), "かな");
+        assert_eq!(to_halfwidth_ascii("ＡＢＣ　１２３"), "ABC 123");
+        assert_eq!(to_halfwidth_ascii("ａ－ｚ！～かな"), "a-z!~かな");
+    }
+}
@@ -0,0 +1,151 @@
+//! Time and peak memory of parsing JMdict entry by entry, compared to parsing the whole document
+//! into a tree
+//!
+//! Run with `cargo bench -p matsuba_server --bench import`. A generated dictionary is used unless
+//! the `JMDICT` environment variable is set to the path of an uncompressed copy of JMdict.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fs::File;
+use std::hint::black_box;
+use std::io::{BufReader, Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use roxmltree::{Document, ParsingOptions};
+
+// the tests of the module are not built into benches, leaving their imports unused
+#[allow(dead_code, unused_imports)]
+#[path = "../src/jmdict.rs"]
+mod jmdict;
+
+use jmdict::JmdictReader;
+
+/// Allocator keeping track of the most memory in use at once
+struct Counting;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let current = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(current, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+const ENTRIES: usize = 200_000;
+
+/// Writes a dictionary shaped like JMdict, using the same DTD entities
+fn generate() -> PathBuf {
+    let path = std::env::temp_dir().join("matsuba-bench-jmdict.xml");
+    let mut file = std::io::BufWriter::new(File::create(&path).unwrap());
+    write!(
+        file,
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<!DOCTYPE JMdict [
+<!ELEMENT JMdict (entry*)>
+<!ENTITY n \"noun (common) (futsuumeishi)\">
+<!ENTITY v5k \"Godan verb with 'ku' ending\">
+<!ENTITY uk \"word usually written using kana alone\">
+]>
+<JMdict>
+"
+    )
+    .unwrap();
+    for i in 0..ENTRIES {
+        write!(
+            file,
+            "<entry>
+<ent_seq>{seq}</ent_seq>
+<k_ele>
+<keb>漢字{i}</keb>
+<ke_pri>ichi1</ke_pri>
+</k_ele>
+<r_ele>
+<reb>かんじ{i}</reb>
+<re_pri>ichi1</re_pri>
+</r_ele>
+<sense>
+<pos>&n;</pos>
+<misc>&uk;</misc>
+<gloss>word number {i}</gloss>
+<gloss>another meaning</gloss>
+</sense>
+</entry>
+",
+            seq = 1_000_000 + i,
+        )
+        .unwrap();
+    }
+    writeln!(file, "</JMdict>").unwrap();
+    path
+}
+
+/// Parses the whole document into a tree, as imports used to
+fn tree(path: &PathBuf) -> usize {
+    let mut text = String::new();
+    File::open(path).unwrap().read_to_string(&mut text).unwrap();
+    let opt = ParsingOptions { allow_dtd: true };
+    let doc = Document::parse_with_options(&text, opt).unwrap();
+    let root = doc.root().last_child().unwrap();
+    root.children()
+        .filter(|n| n.is_element())
+        .filter(|n| black_box(n.descendants().count()) > 0)
+        .count()
+}
+
+/// Parses the document one entry at a time
+fn stream(path: &PathBuf) -> usize {
+    let mut entries = 0;
+    for entry in JmdictReader::new(BufReader::new(File::open(path).unwrap())) {
+        black_box(entry.unwrap());
+        entries += 1;
+    }
+    entries
+}
+
+fn bench(name: &str, path: &PathBuf, parse: fn(&PathBuf) -> usize) {
+    let baseline = CURRENT.load(Ordering::Relaxed);
+    PEAK.store(baseline, Ordering::Relaxed);
+
+    let start = Instant::now();
+    let entries = parse(path);
+    let elapsed = start.elapsed();
+    let peak = PEAK.load(Ordering::Relaxed) - baseline;
+
+    println!(
+        "{:<10} {} entries in {:>10.2?}, peak memory {:>10} KiB",
+        name,
+        entries,
+        elapsed,
+        peak / 1024
+    );
+}
+
+fn main() {
+    let path = match std::env::var_os("JMDICT") {
+        Some(path) => PathBuf::from(path),
+        None => generate(),
+    };
+    println!(
+        "{}: {:.1} MiB",
+        path.display(),
+        std::fs::metadata(&path).unwrap().len() as f64 / (1024.0 * 1024.0)
+    );
+
+    bench("tree", &path, tree);
+    bench("stream", &path, stream);
+}
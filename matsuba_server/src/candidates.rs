@@ -0,0 +1,23 @@
+//! Conversion candidates generated from the input instead of looked up in the dictionary
+
+use chrono::{Datelike, Local, Timelike};
+use matsuba_common::{
+    date::{self, Date, Time},
+    number,
+};
+
+/// Candidates generated for the kana being converted, such as numbers and the current date
+///
+/// Does not include the kana itself.
+pub fn generate(kana: &str) -> Vec<String> {
+    let now = Local::now();
+    let today = Date::new(now.year(), now.month(), now.day());
+    let time = Time {
+        hour: now.hour(),
+        minute: now.minute(),
+    };
+
+    let mut candidates = number::candidates(kana);
+    candidates.extend(date::candidates(kana, today, time));
+    candidates
+}
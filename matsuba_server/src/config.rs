@@ -1,8 +1,16 @@
 // config file for matsuba
 use config::{Config, ConfigError, File};
 use lazy_static::lazy_static;
+use matsuba_common::conversion::{
+    ConversionRule, ConversionTable, InputScheme, PunctuationStyle, Romanization,
+};
+use matsuba_common::converter::{
+    Converter, Dfa, KanaLayout, KanaWidth, NHandling, Transliteration,
+};
 use pino_xmodmap::{FromStr, KeySym, KeyTable, Modifier};
 use serde::{de::Visitor, Deserialize};
+use std::collections::HashMap;
+use std::sync::Arc;
 use x11rb::protocol::xproto::KeyButMask;
 
 lazy_static! {
@@ -15,6 +23,10 @@ pub struct Settings {
     pub keys: KeyMap,
     pub theme: Theme,
     pub database: Database,
+    pub romaji: Romaji,
+    /// Romaji state machine built from the romaji section, shared by every converter
+    #[serde(skip, default = "Dfa::builtin")]
+    pub dfa: Arc<Dfa>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -43,6 +55,86 @@ pub struct Database {
     pub cache_dir: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct Romaji {
+    /// Romanization systems accepted alongside hepburn
+    #[serde(deserialize_with = "deserialize_from_str")]
+    pub romanization: Romanization,
+    /// Extended romaji shortcuts such as AZIK
+    #[serde(deserialize_with = "deserialize_from_str")]
+    pub scheme: InputScheme,
+    /// Characters used for periods and commas
+    #[serde(deserialize_with = "deserialize_from_str")]
+    pub punctuation: PunctuationStyle,
+    /// Width of the kana that is output
+    #[serde(deserialize_with = "deserialize_from_str")]
+    pub kana_width: KanaWidth,
+    /// How a lone n is converted to ん
+    #[serde(deserialize_with = "deserialize_from_str")]
+    pub n_handling: NHandling,
+    /// Keyboard layout used to type kana
+    #[serde(deserialize_with = "deserialize_from_str")]
+    pub layout: KanaLayout,
+    /// Path to a romaji table file that replaces the built-in table
+    pub table: Option<String>,
+    /// Rules to add to the table, overriding any existing rule with the same romaji
+    pub rules: HashMap<String, (String, String)>,
+    /// Romaji of rules to remove from the table
+    pub remove: Vec<String>,
+}
+
+impl Romaji {
+    /// Builds the conversion table described by this section
+    pub fn conversion_table(&self) -> Result<ConversionTable, ConfigError> {
+        let mut table = match &self.table {
+            Some(path) => {
+                let text = std::fs::read_to_string(path).map_err(|e| {
+                    ConfigError::Message(format!("could not read romaji table {}: {}", path, e))
+                })?;
+                ConversionTable::parse(&text).map_err(|e| {
+                    ConfigError::Message(format!("invalid romaji table {}: {}", path, e))
+                })?
+            }
+            None => ConversionTable::default(),
+        };
+        table.add_romanization(self.romanization);
+        table.add_scheme(self.scheme);
+        table.add_punctuation(self.punctuation);
+
+        for romaji in &self.remove {
+            if table.remove(romaji).is_none() {
+                return Err(ConfigError::Message(format!(
+                    "can not remove romaji rule '{}' since it does not exist",
+                    romaji
+                )));
+            }
+        }
+
+        for (romaji, (hiragana, katakana)) in &self.rules {
+            table
+                .insert(ConversionRule::new(romaji, hiragana, katakana))
+                .map_err(|e| ConfigError::Message(format!("invalid romaji rule: {}", e)))?;
+        }
+
+        table
+            .validate()
+            .map_err(|e| ConfigError::Message(format!("invalid romaji table: {}", e)))?;
+
+        Ok(table)
+    }
+}
+
+/// Deserializes any type that can be parsed from a string
+fn deserialize_from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    let value = String::deserialize(deserializer)?;
+    value.parse().map_err(serde::de::Error::custom)
+}
+
 #[derive(Debug, Default)]
 pub struct Color {
     pub r: f32,
@@ -130,6 +222,63 @@ pub struct KeyMap {
     pub next_conversion: Keybinding,
     /// Cycle to the previous conversion
     pub prev_conversion: Keybinding,
+    /// Convert the current input to hiragana
+    pub hiragana: Keybinding,
+    /// Convert the current input to full-width katakana
+    pub katakana: Keybinding,
+    /// Convert the current input to half-width katakana
+    pub halfwidth: Keybinding,
+    /// Convert the current input to the full-width form of the typed keys
+    pub fullwidth_romaji: Keybinding,
+    /// Convert the current input to the typed keys
+    pub romaji: Keybinding,
+    /// Toggle full-width alphanumeric input
+    pub alphanumeric: Keybinding,
+    /// Move the caret one character left
+    pub move_left: Keybinding,
+    /// Move the caret one character right
+    pub move_right: Keybinding,
+    /// Move the caret to the start of the input
+    pub move_home: Keybinding,
+    /// Move the caret to the end of the input
+    pub move_end: Keybinding,
+    /// Delete the character after the caret
+    pub delete_forward: Keybinding,
+    /// Toggle a dakuten on the kana before the caret
+    pub dakuten: Keybinding,
+    /// Toggle a handakuten on the kana before the caret
+    pub handakuten: Keybinding,
+    /// Switch the kana before the caret between its small and large forms
+    pub small_kana: Keybinding,
+}
+
+impl KeyMap {
+    /// Transliteration bound to a keybinding, if any
+    pub fn transliteration(&self, keybinding: &Keybinding) -> Option<Transliteration> {
+        [
+            (&self.hiragana, Transliteration::Hiragana),
+            (&self.katakana, Transliteration::Katakana),
+            (&self.halfwidth, Transliteration::HalfWidthKatakana),
+            (&self.fullwidth_romaji, Transliteration::FullWidthRomaji),
+            (&self.romaji, Transliteration::Romaji),
+        ]
+        .into_iter()
+        .find(|(binding, _)| keybinding.matches(binding))
+        .map(|(_, to)| to)
+    }
+
+    /// Change to the kana before the caret bound to a keybinding, if any
+    pub fn kana_modifier(&self, keybinding: &Keybinding) -> Option<fn(&mut Converter)> {
+        let modifiers: [(&Keybinding, fn(&mut Converter)); 3] = [
+            (&self.dakuten, Converter::toggle_dakuten),
+            (&self.handakuten, Converter::toggle_handakuten),
+            (&self.small_kana, Converter::toggle_small),
+        ];
+        modifiers
+            .into_iter()
+            .find(|(binding, _)| keybinding.matches(binding))
+            .map(|(_, modify)| modify)
+    }
 }
 
 #[derive(Debug)]
@@ -258,7 +407,20 @@ impl Settings {
 
         let conf_built = conf.build()?;
 
-        conf_built.try_deserialize()
+        let mut settings: Settings = conf_built.try_deserialize()?;
+        settings.dfa = Arc::new(Dfa::new(&settings.romaji.conversion_table()?));
+
+        Ok(settings)
+    }
+
+    /// Creates a converter set up according to the romaji section
+    pub fn converter(&self) -> Converter {
+        // table is validated when settings are loaded
+        let mut converter = Converter::with_dfa(self.dfa.clone());
+        converter.kana_width = self.romaji.kana_width;
+        converter.n_handling = self.romaji.n_handling;
+        converter.kana_layout = self.romaji.layout;
+        converter
     }
 }
 
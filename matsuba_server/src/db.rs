@@ -1,29 +1,80 @@
+use std::collections::HashMap;
 use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use matsuba_common::kana;
 use rusqlite::Connection;
-use rusqlite::{params, Result};
+use rusqlite::{params, params_from_iter, Result};
 
 use crate::config::SETTINGS;
 
 pub type DBConnection = Connection;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct Entry {
     pub r_ele: String,
     pub k_ele: String,
-    pub frequency: u8,
+    /// Priority from the JMdict priority markers, higher for more common words
+    pub frequency: u32,
+    /// Sequence number of the JMdict entry, which the senses are stored by
+    pub ent_seq: Option<u32>,
+    /// Meanings of the word, only filled in by [`search`]
+    pub senses: Vec<Sense>,
 }
 
 impl Entry {
-    pub fn new(r_ele: String, k_ele: String) -> Self {
+    pub fn new(r_ele: String, k_ele: String, frequency: u32) -> Self {
         Entry {
             r_ele,
             k_ele,
-            frequency: 0,
+            frequency,
+            ent_seq: None,
+            senses: Vec::new(),
         }
     }
+
+    /// First gloss of the first sense, short enough to show next to a candidate
+    pub fn short_gloss(&self) -> Option<&str> {
+        self.senses
+            .first()
+            .and_then(|sense| sense.gloss.first())
+            .map(String::as_str)
+    }
+}
+
+/// One meaning of a JMdict entry
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Sense {
+    /// Kanji the sense applies to, or all of them if empty
+    pub stagk: Vec<String>,
+    pub gloss: Vec<String>,
+    /// Part of speech tags, such as `n`
+    pub pos: Vec<String>,
+    pub misc: Vec<String>,
+    pub field: Vec<String>,
+}
+
+/// Lists are stored in a single column, separated by newlines which never occur in JMdict text
+fn join(list: &[String]) -> String {
+    list.join("\n")
+}
+
+fn split(column: String) -> Vec<String> {
+    match column.is_empty() {
+        true => Vec::new(),
+        false => column.split('\n').map(String::from).collect(),
+    }
 }
 
+/// Number of selections that a dictionary priority of 100 is worth
+const SELECTION_WEIGHT: f64 = 100.0;
+/// Days after which a selection counts for half as much
+const SELECTION_HALF_LIFE: f64 = 30.0;
+/// Bonus for recently selected entries, worth more than any dictionary priority
+const RECENCY_WEIGHT: f64 = 1000.0;
+/// Days after which the recency bonus is halved
+const RECENCY_HALF_LIFE: f64 = 1.0;
+
 pub fn get_connection() -> Result<Connection> {
     let db_path = Path::new(&SETTINGS.database.cache_dir).join("dict.db3");
     Connection::open(db_path.to_str().unwrap())
@@ -36,7 +87,54 @@ pub fn init(conn: &Connection) -> Result<()> {
             id INTEGER PRIMARY KEY AUTOINCREMENT,
             r_ele TEXT NOT NULL,
             k_ele TEXT NOT NULL,
-            frequency INTEGER DEFAULT 0
+            frequency INTEGER DEFAULT 0,
+            ent_seq INTEGER
+        )
+        ",
+        [],
+    )?;
+    // databases created before senses were stored lack the column
+    if !has_column(conn, "entry", "ent_seq")? {
+        conn.execute("ALTER TABLE entry ADD COLUMN ent_seq INTEGER", [])?;
+    }
+
+    conn.execute(
+        "
+        CREATE TABLE IF NOT EXISTS sense (
+            ent_seq INTEGER NOT NULL,
+            position INTEGER NOT NULL,
+            stagk TEXT NOT NULL,
+            gloss TEXT NOT NULL,
+            pos TEXT NOT NULL,
+            misc TEXT NOT NULL,
+            field TEXT NOT NULL,
+            PRIMARY KEY (ent_seq, position)
+        )
+        ",
+        [],
+    )?;
+
+    // kept apart from the dictionary so that it survives fetching the dictionary again
+    conn.execute(
+        "
+        CREATE TABLE IF NOT EXISTS selection (
+            r_ele TEXT NOT NULL,
+            k_ele TEXT NOT NULL,
+            count INTEGER NOT NULL DEFAULT 0,
+            last_used INTEGER NOT NULL,
+            PRIMARY KEY (r_ele, k_ele)
+        )
+        ",
+        [],
+    )?;
+
+    // words added by the user, also kept apart from the dictionary
+    conn.execute(
+        "
+        CREATE TABLE IF NOT EXISTS user_entry (
+            r_ele TEXT NOT NULL,
+            k_ele TEXT NOT NULL,
+            PRIMARY KEY (r_ele, k_ele)
         )
         ",
         [],
@@ -45,30 +143,142 @@ pub fn init(conn: &Connection) -> Result<()> {
     Ok(())
 }
 
+/// Removes every dictionary entry, keeping the user dictionary and what was learned
+pub fn clear_entries(conn: &Connection) -> Result<()> {
+    conn.execute("DELETE FROM entry", [])?;
+    conn.execute("DELETE FROM sense", [])?;
+
+    Ok(())
+}
+
 pub fn insert_entry(conn: &Connection, entry: &Entry) -> Result<()> {
     conn.execute(
         "
-        INSERT INTO entry (r_ele, k_ele)
-        VALUES (?1, ?2)
+        INSERT INTO entry (r_ele, k_ele, frequency, ent_seq)
+        VALUES (?1, ?2, ?3, ?4)
         ",
-        params![entry.r_ele, entry.k_ele],
+        params![entry.r_ele, entry.k_ele, entry.frequency, entry.ent_seq],
     )?;
 
     Ok(())
 }
 
-pub fn search(conn: &Connection, reading: &str) -> Result<Vec<Entry>> {
+/// Stores the senses of a JMdict entry, in order
+pub fn insert_senses(conn: &Connection, ent_seq: u32, senses: &[Sense]) -> Result<()> {
+    for (position, sense) in senses.iter().enumerate() {
+        conn.execute(
+            "
+            INSERT INTO sense (ent_seq, position, stagk, gloss, pos, misc, field)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            ",
+            params![
+                ent_seq,
+                position,
+                join(&sense.stagk),
+                join(&sense.gloss),
+                join(&sense.pos),
+                join(&sense.misc),
+                join(&sense.field)
+            ],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Senses of several JMdict entries, in order for each entry
+fn senses(conn: &Connection, ent_seqs: &[u32]) -> Result<HashMap<u32, Vec<Sense>>> {
+    let mut output: HashMap<u32, Vec<Sense>> = HashMap::new();
+    if ent_seqs.is_empty() {
+        return Ok(output);
+    }
+
+    let placeholders = vec!["?"; ent_seqs.len()].join(", ");
+    let mut query = conn.prepare(&format!(
+        "
+        SELECT ent_seq, stagk, gloss, pos, misc, field
+        FROM sense
+        WHERE ent_seq IN ({})
+        ORDER BY ent_seq, position
+        ",
+        placeholders
+    ))?;
+
+    let sense_it = query.query_map(params_from_iter(ent_seqs), |row| {
+        let sense = Sense {
+            stagk: split(row.get(1)?),
+            gloss: split(row.get(2)?),
+            pos: split(row.get(3)?),
+            misc: split(row.get(4)?),
+            field: split(row.get(5)?),
+        };
+        Ok((row.get(0)?, sense))
+    })?;
+
+    for sense in sense_it {
+        let (ent_seq, sense) = sense?;
+        output.entry(ent_seq).or_default().push(sense);
+    }
+
+    Ok(output)
+}
+
+/// Whether a table has a column, for upgrading databases created by older versions
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let mut query = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let column_it = query.query_map([], |row| row.get::<_, String>(1))?;
+
+    for name in column_it {
+        if name? == column {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Adds a word to the user dictionary
+///
+/// Returns false if the word was already in the user dictionary.
+pub fn add_user_word(conn: &Connection, reading: &str, word: &str) -> Result<bool> {
+    let inserted = conn.execute(
+        "
+        INSERT OR IGNORE INTO user_entry (r_ele, k_ele)
+        VALUES (?1, ?2)
+        ",
+        params![kana::fold(reading), word],
+    )?;
+
+    Ok(inserted > 0)
+}
+
+/// Removes a word from the user dictionary
+///
+/// Returns false if the word was not in the user dictionary.
+pub fn remove_user_word(conn: &Connection, reading: &str, word: &str) -> Result<bool> {
+    let removed = conn.execute(
+        "
+        DELETE FROM user_entry
+        WHERE r_ele = ?1 AND k_ele = ?2
+        ",
+        params![kana::fold(reading), word],
+    )?;
+
+    Ok(removed > 0)
+}
+
+/// All words in the user dictionary, ordered by reading
+pub fn list_user_words(conn: &Connection) -> Result<Vec<Entry>> {
     let mut query = conn.prepare(
         "
-        SELECT r_ele, k_ele, frequency
-        FROM entry
-        WHERE r_ele = ?
+        SELECT r_ele, k_ele
+        FROM user_entry
+        ORDER BY r_ele, k_ele
         ",
     )?;
 
-    let entry_it = query.query_map(&[reading], |row| Ok(Entry::new(row.get(0)?, row.get(1)?)))?;
+    let entry_it = query.query_map([], |row| Ok(Entry::new(row.get(0)?, row.get(1)?, 0)))?;
 
-    // TODO wonder if this can be better
     let mut output: Vec<Entry> = Vec::new();
     for entry in entry_it {
         output.push(entry?);
@@ -76,3 +286,340 @@ pub fn search(conn: &Connection, reading: &str) -> Result<Vec<Entry>> {
 
     Ok(output)
 }
+
+/// Remembers that a conversion was chosen for a reading, so that it ranks higher next time
+pub fn record_selection(conn: &Connection, reading: &str, k_ele: &str) -> Result<()> {
+    conn.execute(
+        "
+        INSERT INTO selection (r_ele, k_ele, count, last_used)
+        VALUES (?1, ?2, 1, ?3)
+        ON CONFLICT (r_ele, k_ele) DO UPDATE SET count = count + 1, last_used = ?3
+        ",
+        params![kana::fold(reading), k_ele, now()],
+    )?;
+
+    Ok(())
+}
+
+/// Seconds since the unix epoch
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Weight of something that halves every `half_life` days, after `age` seconds
+fn decay(age: i64, half_life: f64) -> f64 {
+    let days = age.max(0) as f64 / (24.0 * 60.0 * 60.0);
+    0.5f64.powf(days / half_life)
+}
+
+/// Score used to rank an entry, blending its dictionary priority with how often and how recently
+/// it was selected
+///
+/// `age` is the number of seconds since it was last selected, if ever.
+fn rank_score(frequency: u32, count: u32, age: Option<i64>) -> f64 {
+    let learned = match age {
+        Some(age) => {
+            SELECTION_WEIGHT * count as f64 * decay(age, SELECTION_HALF_LIFE)
+                + RECENCY_WEIGHT * decay(age, RECENCY_HALF_LIFE)
+        }
+        None => 0.0,
+    };
+    frequency as f64 + learned
+}
+
+/// Looks up entries by reading, regardless of whether it is typed in hiragana or katakana of any
+/// width
+///
+/// Words from the user dictionary come first, followed by the most common words of the
+/// dictionary. Within each, words that were selected before are moved up depending on how often
+/// and how recently they were selected.
+pub fn search(conn: &Connection, reading: &str) -> Result<Vec<Entry>> {
+    let hiragana = kana::fold(reading);
+    let katakana = kana::to_katakana(&hiragana);
+
+    let mut query = conn.prepare(
+        "
+        SELECT candidate.r_ele, candidate.k_ele, candidate.frequency, candidate.user,
+            selection.count, selection.last_used, candidate.ent_seq
+        FROM (
+            SELECT r_ele, k_ele, 0 AS frequency, 1 AS user, rowid AS id, NULL AS ent_seq
+            FROM user_entry
+            WHERE r_ele = ?1
+            UNION ALL
+            SELECT r_ele, k_ele, frequency, 0 AS user, id, ent_seq
+            FROM entry
+            WHERE r_ele = ?1 OR r_ele = ?2
+        ) AS candidate
+        LEFT JOIN selection ON selection.r_ele = ?1 AND selection.k_ele = candidate.k_ele
+        ORDER BY candidate.user DESC, candidate.frequency DESC, candidate.id ASC
+        ",
+    )?;
+
+    let now = now();
+    let entry_it = query.query_map([hiragana.as_str(), katakana.as_str()], |row| {
+        let entry = Entry {
+            ent_seq: row.get(6)?,
+            ..Entry::new(row.get(0)?, row.get(1)?, row.get(2)?)
+        };
+        let user: bool = row.get(3)?;
+        let count: Option<u32> = row.get(4)?;
+        let last_used: Option<i64> = row.get(5)?;
+        let score = rank_score(
+            entry.frequency,
+            count.unwrap_or(0),
+            last_used.map(|t| now - t),
+        );
+        Ok((entry, user, score))
+    })?;
+
+    let mut output: Vec<(Entry, bool, f64)> = Vec::new();
+    for entry in entry_it {
+        output.push(entry?);
+    }
+    // stable, so entries with the same score keep the dictionary order
+    output
+        .sort_by(|(_, a_user, a), (_, b_user, b)| b_user.cmp(a_user).then_with(|| b.total_cmp(a)));
+
+    // words in both the user dictionary and the dictionary, or in several dictionary entries, are
+    // only listed once, with the senses of every entry they are in
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut entry_seqs: Vec<Vec<u32>> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for (entry, _, _) in output {
+        match positions.get(&entry.k_ele) {
+            Some(&i) => {
+                if let Some(ent_seq) = entry.ent_seq {
+                    entries[i].ent_seq.get_or_insert(ent_seq);
+                    if !entry_seqs[i].contains(&ent_seq) {
+                        entry_seqs[i].push(ent_seq);
+                    }
+                }
+            }
+            None => {
+                positions.insert(entry.k_ele.clone(), entries.len());
+                entry_seqs.push(entry.ent_seq.into_iter().collect());
+                entries.push(entry);
+            }
+        }
+    }
+
+    // senses of every entry are fetched at once, then given to the kanji they apply to
+    let senses = senses(conn, &entry_seqs.concat())?;
+    for (entry, ent_seqs) in entries.iter_mut().zip(entry_seqs) {
+        entry.senses = ent_seqs
+            .iter()
+            .filter_map(|ent_seq| senses.get(ent_seq))
+            .flatten()
+            .filter(|sense| sense.stagk.is_empty() || sense.stagk.contains(&entry.k_ele))
+            .cloned()
+            .collect();
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rusqlite::{params, Connection};
+
+    use super::{
+        add_user_word, clear_entries, init, insert_entry, insert_senses, list_user_words,
+        rank_score, record_selection, remove_user_word, search, Entry, Sense,
+    };
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    #[test]
+    fn score() {
+        // selections fade over time
+        assert!(rank_score(0, 1, Some(0)) > rank_score(0, 1, Some(7 * DAY)));
+        assert!(rank_score(0, 5, Some(90 * DAY)) < rank_score(0, 5, Some(DAY)));
+        // a word chosen just now beats the most common word
+        assert!(rank_score(0, 1, Some(60)) > rank_score(300, 0, None));
+        // but a single choice made long ago does not
+        assert!(rank_score(0, 1, Some(365 * DAY)) < rank_score(100, 0, None));
+        // choosing often keeps a word ahead of a more common one
+        assert!(rank_score(50, 10, Some(20 * DAY)) > rank_score(300, 0, None));
+    }
+
+    #[test]
+    fn learning() {
+        let conn = Connection::open_in_memory().unwrap();
+        init(&conn).unwrap();
+        for (k_ele, frequency) in [("橋", 288), ("端", 166), ("箸", 100)] {
+            insert_entry(&conn, &Entry::new("はし".into(), k_ele.into(), frequency)).unwrap();
+        }
+        let kanji = |conn: &Connection| {
+            search(conn, "はし")
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.k_ele)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(kanji(&conn), vec!["橋", "端", "箸"]);
+
+        record_selection(&conn, "はし", "箸").unwrap();
+        assert_eq!(kanji(&conn), vec!["箸", "橋", "端"]);
+        // readings are folded, so katakana input learns the same way
+        record_selection(&conn, "ハシ", "端").unwrap();
+        assert_eq!(kanji(&conn), vec!["端", "箸", "橋"]);
+
+        // old selections fade back to the dictionary order
+        conn.execute(
+            "UPDATE selection SET last_used = last_used - ?1",
+            params![400 * DAY],
+        )
+        .unwrap();
+        assert_eq!(kanji(&conn), vec!["橋", "端", "箸"]);
+
+        // fetching the dictionary again keeps what was learned
+        clear_entries(&conn).unwrap();
+        insert_entry(&conn, &Entry::new("はし".into(), "橋".into(), 288)).unwrap();
+        insert_entry(&conn, &Entry::new("はし".into(), "箸".into(), 100)).unwrap();
+        record_selection(&conn, "はし", "箸").unwrap();
+        let count: u32 = conn
+            .query_row(
+                "SELECT count FROM selection WHERE r_ele = 'はし' AND k_ele = '箸'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(kanji(&conn), vec!["箸", "橋"]);
+    }
+
+    #[test]
+    fn user_dictionary() {
+        let conn = Connection::open_in_memory().unwrap();
+        init(&conn).unwrap();
+        insert_entry(&conn, &Entry::new("まつば".into(), "松葉".into(), 100)).unwrap();
+        let kanji = |conn: &Connection| {
+            search(conn, "まつば")
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.k_ele)
+                .collect::<Vec<_>>()
+        };
+
+        assert!(add_user_word(&conn, "まつば", "matsuba").unwrap());
+        assert!(add_user_word(&conn, "マツバ", "マツバ").unwrap());
+        assert!(!add_user_word(&conn, "まつば", "matsuba").unwrap());
+        assert_eq!(kanji(&conn), vec!["matsuba", "マツバ", "松葉"]);
+
+        // learning only reorders words within the user dictionary and the dictionary
+        record_selection(&conn, "まつば", "松葉").unwrap();
+        record_selection(&conn, "まつば", "マツバ").unwrap();
+        assert_eq!(kanji(&conn), vec!["マツバ", "matsuba", "松葉"]);
+
+        // a user word that is also in the dictionary is listed once, as a user word
+        assert!(add_user_word(&conn, "まつば", "松葉").unwrap());
+        assert_eq!(kanji(&conn), vec!["マツバ", "松葉", "matsuba"]);
+
+        let words = list_user_words(&conn)
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.r_ele, entry.k_ele))
+            .collect::<Vec<_>>();
+        assert_eq!(words.len(), 3);
+        assert!(words.iter().all(|(r_ele, _)| r_ele == "まつば"));
+
+        assert!(remove_user_word(&conn, "まつば", "matsuba").unwrap());
+        assert!(!remove_user_word(&conn, "まつば", "matsuba").unwrap());
+        assert_eq!(kanji(&conn), vec!["マツバ", "松葉"]);
+    }
+
+    #[test]
+    fn senses() {
+        let conn = Connection::open_in_memory().unwrap();
+        // databases from before senses were stored are upgraded
+        conn.execute(
+            "CREATE TABLE entry (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                r_ele TEXT NOT NULL,
+                k_ele TEXT NOT NULL,
+                frequency INTEGER DEFAULT 0
+            )",
+            [],
+        )
+        .unwrap();
+        init(&conn).unwrap();
+
+        for k_ele in ["書く", "描く"] {
+            let entry = Entry {
+                ent_seq: Some(1000000),
+                ..Entry::new("かく".into(), k_ele.into(), 0)
+            };
+            insert_entry(&conn, &entry).unwrap();
+        }
+        let write = Sense {
+            gloss: vec!["to write".into(), "to compose".into()],
+            pos: vec!["v5k".into(), "vt".into()],
+            ..Default::default()
+        };
+        let paint = Sense {
+            stagk: vec!["描く".into()],
+            gloss: vec!["to paint".into()],
+            misc: vec!["uk".into()],
+            ..Default::default()
+        };
+        insert_senses(&conn, 1000000, &[write.clone(), paint.clone()]).unwrap();
+        add_user_word(&conn, "かく", "核").unwrap();
+
+        let entries = search(&conn, "かく").unwrap();
+        let senses = entries
+            .iter()
+            .map(|entry| (entry.k_ele.as_str(), entry.senses.clone()))
+            .collect::<Vec<_>>();
+        // senses restricted to some kanji are only given for them
+        assert_eq!(
+            senses,
+            vec![
+                ("核", vec![]),
+                ("書く", vec![write.clone()]),
+                ("描く", vec![write, paint]),
+            ]
+        );
+        assert_eq!(entries[0].short_gloss(), None);
+        assert_eq!(entries[1].short_gloss(), Some("to write"));
+
+        clear_entries(&conn).unwrap();
+        let count: u32 = conn
+            .query_row("SELECT COUNT(*) FROM sense", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn duplicate_senses() {
+        let conn = Connection::open_in_memory().unwrap();
+        init(&conn).unwrap();
+
+        let needle = Sense {
+            gloss: vec!["pine needle".into()],
+            ..Default::default()
+        };
+        let crutch = Sense {
+            gloss: vec!["crutch".into()],
+            ..Default::default()
+        };
+        // the same kanji in two dictionary entries
+        for (ent_seq, sense) in [(1000000, &needle), (1000001, &crutch)] {
+            let entry = Entry {
+                ent_seq: Some(ent_seq),
+                ..Entry::new("まつば".into(), "松葉".into(), 0)
+            };
+            insert_entry(&conn, &entry).unwrap();
+            insert_senses(&conn, ent_seq, std::slice::from_ref(sense)).unwrap();
+        }
+        // listed first, but without senses of its own
+        add_user_word(&conn, "まつば", "松葉").unwrap();
+
+        let entries = search(&conn, "まつば").unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].ent_seq, Some(1000000));
+        assert_eq!(entries[0].senses, vec![needle, crutch]);
+    }
+}
@@ -0,0 +1,346 @@
+//! Streaming reader for the JMdict xml
+//!
+//! Entries are read one at a time instead of parsing the whole document, so that memory use
+//! stays the same however large the dictionary is. Entities declared in the DTD of the document
+//! are expanded in text, except in tag elements such as `pos` where the entity name itself (`n`
+//! for `&n;`) is kept, as that is how tags are named elsewhere.
+
+use std::collections::HashMap;
+use std::io::BufRead;
+
+use quick_xml::events::{BytesText, Event};
+use quick_xml::Reader;
+
+/// `k_ele` element, a way of writing the word with kanji
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct KanjiElement {
+    pub keb: String,
+    pub ke_pri: Vec<String>,
+}
+
+/// `r_ele` element, a reading of the word
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReadingElement {
+    pub reb: String,
+    /// Kanji elements the reading applies to, or all of them if empty
+    pub re_restr: Vec<String>,
+    pub re_pri: Vec<String>,
+}
+
+/// `sense` element, one meaning of the word
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Sense {
+    /// Kanji elements the sense applies to, or all of them if empty
+    pub stagk: Vec<String>,
+    /// English glosses, glosses in other languages are left out
+    pub gloss: Vec<String>,
+    pub pos: Vec<String>,
+    pub field: Vec<String>,
+    pub misc: Vec<String>,
+}
+
+/// `entry` element
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct JmdictEntry {
+    pub ent_seq: u32,
+    pub k_ele: Vec<KanjiElement>,
+    pub r_ele: Vec<ReadingElement>,
+    pub sense: Vec<Sense>,
+}
+
+impl JmdictEntry {
+    /// Stores the text of a child element of the entry
+    fn set(&mut self, element: &str, text: String) {
+        let kanji = self.k_ele.last_mut();
+        let reading = self.r_ele.last_mut();
+        let sense = self.sense.last_mut();
+        match (element, kanji, reading, sense) {
+            ("ent_seq", _, _, _) => self.ent_seq = text.parse().unwrap_or_default(),
+            ("keb", Some(k), _, _) => k.keb = text,
+            ("ke_pri", Some(k), _, _) => k.ke_pri.push(text),
+            ("reb", _, Some(r), _) => r.reb = text,
+            ("re_restr", _, Some(r), _) => r.re_restr.push(text),
+            ("re_pri", _, Some(r), _) => r.re_pri.push(text),
+            ("stagk", _, _, Some(s)) => s.stagk.push(text),
+            ("gloss", _, _, Some(s)) => s.gloss.push(text),
+            ("pos", _, _, Some(s)) => s.pos.push(text),
+            ("field", _, _, Some(s)) => s.field.push(text),
+            ("misc", _, _, Some(s)) => s.misc.push(text),
+            _ => {}
+        }
+    }
+}
+
+/// Elements whose text is an entity standing for a tag
+fn is_tag(element: &str) -> bool {
+    matches!(element, "pos" | "field" | "misc")
+}
+
+/// Reads the entity declarations of a DTD, such as `<!ENTITY n "noun (common) (futsuumeishi)">`
+fn parse_entities(dtd: &str) -> HashMap<String, String> {
+    let mut entities = HashMap::new();
+    for declaration in dtd.split("<!ENTITY").skip(1) {
+        let declaration = declaration.trim_start();
+        let name_end = declaration
+            .find(char::is_whitespace)
+            .unwrap_or(declaration.len());
+        let (name, rest) = declaration.split_at(name_end);
+        let rest = rest.trim_start();
+        let quote = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => quote,
+            _ => continue,
+        };
+        if let Some(value_end) = rest[1..].find(quote) {
+            entities.insert(name.to_string(), rest[1..value_end + 1].to_string());
+        }
+    }
+    entities
+}
+
+/// Iterator over the entries of a JMdict document
+pub struct JmdictReader<R: BufRead> {
+    reader: Reader<R>,
+    buf: Vec<u8>,
+    entities: HashMap<String, String>,
+}
+
+impl<R: BufRead> JmdictReader<R> {
+    pub fn new(source: R) -> Self {
+        let mut reader = Reader::from_reader(source);
+        reader.trim_text(true);
+        JmdictReader {
+            reader,
+            buf: Vec::new(),
+            entities: HashMap::new(),
+        }
+    }
+
+    /// Text of a tag element, which is the name of the entity it uses if any
+    fn tag_text(&self, text: &BytesText) -> quick_xml::Result<String> {
+        let raw = self.reader.decoder().decode(text)?;
+        match raw.strip_prefix('&').and_then(|s| s.strip_suffix(';')) {
+            Some(name) if !name.starts_with('#') => Ok(name.to_string()),
+            _ => self.text(text),
+        }
+    }
+
+    /// Text with every entity expanded
+    fn text(&self, text: &BytesText) -> quick_xml::Result<String> {
+        let unescaped = text.unescape_with(|name| self.entities.get(name).map(String::as_str))?;
+        Ok(unescaped.into_owned())
+    }
+
+    fn read_entry(&mut self) -> quick_xml::Result<Option<JmdictEntry>> {
+        let mut entry: Option<JmdictEntry> = None;
+        // child element of the entry that text is currently read for
+        let mut element: Option<String> = None;
+        let mut text = String::new();
+
+        loop {
+            self.buf.clear();
+            // the buffer is moved out so that events borrowing it do not borrow self
+            let mut buf = std::mem::take(&mut self.buf);
+            let event = self.reader.read_event_into(&mut buf)?;
+            match event {
+                Event::DocType(doctype) => {
+                    let dtd = self.reader.decoder().decode(&doctype)?;
+                    self.entities = parse_entities(&dtd);
+                }
+                Event::Start(start) => {
+                    let name = start.name();
+                    let name = self.reader.decoder().decode(name.as_ref())?;
+                    // glosses without a language are in english
+                    let english = match start.try_get_attribute("xml:lang")? {
+                        Some(lang) => lang.value.as_ref() == b"eng",
+                        None => true,
+                    };
+                    match (name.as_ref(), entry.as_mut()) {
+                        ("entry", _) => entry = Some(JmdictEntry::default()),
+                        ("k_ele", Some(entry)) => entry.k_ele.push(KanjiElement::default()),
+                        ("r_ele", Some(entry)) => entry.r_ele.push(ReadingElement::default()),
+                        ("sense", Some(entry)) => entry.sense.push(Sense::default()),
+                        ("gloss", Some(_)) if !english => {}
+                        (name, Some(_)) => {
+                            element = Some(name.to_string());
+                            text.clear();
+                        }
+                        _ => {}
+                    }
+                }
+                Event::Text(content) => {
+                    if let Some(element) = &element {
+                        match is_tag(element) {
+                            true => text.push_str(&self.tag_text(&content)?),
+                            false => text.push_str(&self.text(&content)?),
+                        }
+                    }
+                }
+                Event::End(end) => {
+                    let name = end.name();
+                    let name = self.reader.decoder().decode(name.as_ref())?;
+                    if name == "entry" {
+                        self.buf = buf;
+                        return Ok(entry);
+                    }
+                    if let (Some(entry), Some(element)) = (entry.as_mut(), element.take()) {
+                        entry.set(&element, std::mem::take(&mut text));
+                    }
+                }
+                Event::Eof => {
+                    self.buf = buf;
+                    return Ok(None);
+                }
+                _ => {}
+            }
+            self.buf = buf;
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for JmdictReader<R> {
+    type Item = quick_xml::Result<JmdictEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_entry().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{parse_entities, JmdictEntry, JmdictReader, KanjiElement, ReadingElement, Sense};
+
+    const JMDICT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE JMdict [
+<!ELEMENT JMdict (entry*)>
+<!-- <entry> is one word, see the documentation -->
+<!ELEMENT entry (ent_seq, k_ele*, r_ele+, sense+)>
+<!ENTITY n "noun (common) (futsuumeishi)">
+<!ENTITY v5k 'Godan verb with &apos;ku&apos; ending'>
+<!ENTITY uk "word usually written using kana alone">
+]>
+<!-- JMdict created: 2024-01-01 -->
+<JMdict>
+<entry>
+<ent_seq>1000000</ent_seq>
+<k_ele>
+<keb>書く</keb>
+<ke_pri>ichi1</ke_pri>
+</k_ele>
+<k_ele>
+<keb>描く</keb>
+</k_ele>
+<r_ele>
+<reb>かく</reb>
+<re_restr>書く</re_restr>
+<re_pri>ichi1</re_pri>
+</r_ele>
+<r_ele>
+<reb>えがく</reb>
+<re_nokanji/>
+</r_ele>
+<sense>
+<pos>&v5k;</pos>
+<misc>&uk;</misc>
+<gloss>to write &amp; draw</gloss>
+<gloss xml:lang="ger">schreiben</gloss>
+<gloss xml:lang="eng" g_type="fig">to compose</gloss>
+</sense>
+<sense>
+<stagk>描く</stagk>
+<gloss>to paint</gloss>
+</sense>
+</entry>
+<entry>
+<ent_seq>1000001</ent_seq>
+<r_ele>
+<reb>ある</reb>
+</r_ele>
+<sense>
+<pos>&n;</pos>
+<gloss xml:lang="dut">zijn</gloss>
+</sense>
+</entry>
+</JMdict>
+"#;
+
+    #[test]
+    fn entities() {
+        let entities = parse_entities(
+            r#" JMdict [ <!ENTITY n "noun (common)"> <!ENTITY  adj-i  'adjective "i"'> ]"#,
+        );
+        assert_eq!(entities["n"], "noun (common)");
+        assert_eq!(entities["adj-i"], "adjective \"i\"");
+        assert_eq!(entities.len(), 2);
+    }
+
+    #[test]
+    fn entries() {
+        let entries = JmdictReader::new(JMDICT.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                JmdictEntry {
+                    ent_seq: 1000000,
+                    k_ele: vec![
+                        KanjiElement {
+                            keb: "書く".into(),
+                            ke_pri: vec!["ichi1".into()],
+                        },
+                        KanjiElement {
+                            keb: "描く".into(),
+                            ..Default::default()
+                        },
+                    ],
+                    r_ele: vec![
+                        ReadingElement {
+                            reb: "かく".into(),
+                            re_restr: vec!["書く".into()],
+                            re_pri: vec!["ichi1".into()],
+                        },
+                        ReadingElement {
+                            reb: "えがく".into(),
+                            ..Default::default()
+                        },
+                    ],
+                    sense: vec![
+                        Sense {
+                            gloss: vec!["to write & draw".into(), "to compose".into()],
+                            pos: vec!["v5k".into()],
+                            misc: vec!["uk".into()],
+                            ..Default::default()
+                        },
+                        Sense {
+                            stagk: vec!["描く".into()],
+                            gloss: vec!["to paint".into()],
+                            ..Default::default()
+                        },
+                    ],
+                },
+                JmdictEntry {
+                    ent_seq: 1000001,
+                    r_ele: vec![ReadingElement {
+                        reb: "ある".into(),
+                        ..Default::default()
+                    }],
+                    sense: vec![Sense {
+                        pos: vec!["n".into()],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        let mut reader = JmdictReader::new("<JMdict><entry><keb>&nope;</keb></entry>".as_bytes());
+        assert!(matches!(reader.next(), Some(Err(_))));
+
+        let mut reader = JmdictReader::new("<JMdict><entry><k_ele></r_ele>".as_bytes());
+        assert!(matches!(reader.next(), Some(Err(_))));
+    }
+}
@@ -1,12 +1,14 @@
+mod candidates;
 mod config;
 mod db;
 mod error;
+mod jmdict;
 mod output;
 mod renderer;
 mod service;
 mod xmlparse;
 
-use log::info;
+use log::{error, info};
 use tonic::transport::Server;
 
 use crate::config::SETTINGS;
@@ -16,6 +18,11 @@ use crate::service::{MatsubaServer, MatsubaService};
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::builder().format_timestamp(None).init();
 
+    // databases created before the selection and user tables existed need them for searching
+    if let Err(e) = db::get_connection().and_then(|conn| db::init(&conn)) {
+        error!("could not initialize database: {}", e);
+    }
+
     tokio::spawn(async move {
         // manually trigger lazy static call (sorta hacky)
         let listen_address = &SETTINGS.server.listen_address;
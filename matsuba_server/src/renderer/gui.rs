@@ -5,6 +5,11 @@ use crate::config::SETTINGS;
 
 use super::IMEState;
 
+/// Size of a gloss relative to the conversion it follows
+const GLOSS_SCALE: f32 = 0.5;
+/// Space between a conversion and its gloss
+const GLOSS_SEPARATOR: &str = "  ";
+
 pub(crate) struct GUIState {
     surface: wgpu::Surface,
     device: wgpu::Device,
@@ -19,6 +24,24 @@ pub(crate) struct GUIState {
 }
 
 impl GUIState {
+    /// Horizontal space taken by text drawn at the given scale
+    pub fn advance(&self, text: &str, scale: f32) -> f32 {
+        let scaled_font = self.font.as_scaled(scale);
+        text.chars()
+            .map(|c| scaled_font.h_advance(self.font.glyph_id(c)))
+            .sum()
+    }
+
+    /// Horizontal space taken by a conversion and its gloss, if any
+    pub fn conversion_width(&self, conversion: &str, gloss: &str) -> f32 {
+        let mut width = self.advance(conversion, self.font_scale);
+        if !gloss.is_empty() {
+            width += self.advance(GLOSS_SEPARATOR, self.font_scale);
+            width += self.advance(gloss, self.font_scale * GLOSS_SCALE);
+        }
+        width
+    }
+
     pub async fn new(window: &Window) -> Self {
         let size = window.inner_size();
 
@@ -113,6 +136,7 @@ impl GUIState {
         let completion_fg = SETTINGS.theme.completion_fg.as_slice_rgba();
         let selected_bg = Vector3::from(SETTINGS.theme.selected_bg.as_slice_rgb());
         let selected_fg = SETTINGS.theme.selected_fg.as_slice_rgba();
+        let caret_color = Vector3::from(SETTINGS.theme.completion_fg.as_slice_rgb());
 
         let output = self.surface.get_current_texture()?;
         let mut view = output
@@ -148,7 +172,7 @@ impl GUIState {
         use cgmath::Vector3;
         use pino_wgpu_shape::Instance;
 
-        let instances = vec![
+        let mut instances = vec![
             // selected conversion hightlight
             Instance {
                 position: Vector3::new(0., 1. - 1. / columns, 0.),
@@ -167,6 +191,39 @@ impl GUIState {
                 color: selected_bg,
             },
         ];
+
+        if let Some(preedit) = &ime_state.preedit {
+            let advance = |text: &str| self.advance(text, self.font_scale);
+            let width = self.size.width as f32;
+            let height = self.size.height as f32;
+            let before = advance(&preedit.before);
+            let pending = advance(&preedit.pending);
+
+            // underline pending romaji that is not converted yet
+            if !preedit.pending.is_empty() {
+                instances.push(Instance {
+                    position: Vector3::new(
+                        -1. + (2. * before + pending) / width,
+                        1. - 2. / columns + 2. / height,
+                        0.,
+                    ),
+                    scale: Vector3::new(pending / width, 1. / height, 1.),
+                    color: caret_color,
+                });
+            }
+
+            // caret after the pending romaji
+            instances.push(Instance {
+                position: Vector3::new(
+                    -1. + (2. * (before + pending) + 1.) / width,
+                    1. - 1. / columns,
+                    0.,
+                ),
+                scale: Vector3::new(1. / width, 1. / columns, 1.),
+                color: caret_color,
+            });
+        }
+
         for instance in instances {
             self.shape_renderer.queue(instance);
         }
@@ -195,13 +252,30 @@ impl GUIState {
             } else {
                 fg_color
             };
+            let mut text = vec![wgpu_glyph::Text::new(conversion)
+                .with_color(text_color)
+                .with_scale(self.font_scale)];
+
+            // short meaning of the conversion in smaller text after it
+            if let Some(gloss) = ime_state.glosses.get(i).filter(|gloss| !gloss.is_empty()) {
+                text.push(
+                    wgpu_glyph::Text::new(GLOSS_SEPARATOR)
+                        .with_color(text_color)
+                        .with_scale(self.font_scale),
+                );
+                text.push(
+                    wgpu_glyph::Text::new(gloss)
+                        .with_color(text_color)
+                        .with_scale(self.font_scale * GLOSS_SCALE),
+                );
+            }
+
             self.glyph_brush.queue(wgpu_glyph::Section {
                 screen_position: (0., scaled_font.height() * ((i as f32) + 1.)),
                 bounds: (self.size.width as f32, self.size.height as f32),
-                text: vec![wgpu_glyph::Text::new(conversion)
-                    .with_color(text_color)
-                    .with_scale(self.font_scale)],
-                ..wgpu_glyph::Section::default()
+                text,
+                // long glosses are cut off rather than wrapped onto the next conversion
+                layout: wgpu_glyph::Layout::default_single_line(),
             });
         }
 
@@ -19,17 +19,25 @@ use winit::{
 use x11rb::protocol::xproto::KeyButMask;
 
 use crate::{
+    candidates,
     config::{Keybinding, SETTINGS},
     db,
 };
 use crate::{output, renderer::gui::GUIState};
 
-use matsuba_common::converter::Converter;
+use matsuba_common::converter::{Converter, InputMode, Preedit};
+use matsuba_common::width::to_fullwidth_ascii;
 
 pub(crate) struct IMEState {
     pub selected_conversion: usize,
     pub conversions: Vec<String>,
+    /// Short meaning of each conversion, empty for conversions not from the dictionary
+    pub glosses: Vec<String>,
+    /// Number of conversions at the start of the list that were found in the dictionary
+    pub dictionary_conversions: usize,
     pub output: String,
+    /// Preedit shown as the output, if the output is being edited
+    pub preedit: Option<Preedit>,
     pub henkan: bool,
 }
 
@@ -38,15 +46,38 @@ impl IMEState {
         IMEState {
             selected_conversion: 0,
             conversions: vec![],
+            glosses: vec![],
+            dictionary_conversions: 0,
             output: String::new(),
+            preedit: None,
             henkan: false,
         }
     }
 
     pub fn clear_conversions(&mut self) {
         self.conversions.clear();
+        self.glosses.clear();
+        self.dictionary_conversions = 0;
         self.selected_conversion = 0;
     }
+
+    /// Adds a conversion option along with its meaning, if known
+    pub fn push_conversion(&mut self, conversion: String, gloss: Option<String>) {
+        self.conversions.push(conversion);
+        self.glosses.push(gloss.unwrap_or_default());
+    }
+
+    /// Shows the preedit of the converter as the output
+    pub fn set_preedit(&mut self, converter: &Converter) {
+        let preedit = converter.preedit();
+        self.output = preedit.to_string();
+        self.preedit = Some(preedit);
+    }
+
+    pub fn clear_output(&mut self) {
+        self.output = String::new();
+        self.preedit = None;
+    }
 }
 
 pub async fn run() {
@@ -66,7 +97,7 @@ pub async fn run() {
     let mut gui_state = GUIState::new(&window).await;
 
     let mut ime_state = IMEState::new();
-    let mut converter = Converter::new();
+    let mut converter = SETTINGS.converter();
 
     let xsession = xorg::XSession::new().expect("failed getting xsession");
     xsession.configure_root().expect("could not configure root");
@@ -135,36 +166,44 @@ pub async fn run() {
                     // TOOD duplicate of return rn
                     converter.accept();
                     ime_state.clear_conversions();
-                    ime_state.conversions.clear();
-                    ime_state.selected_conversion = 0;
                     update_size(&gui_state, &ime_state, &window);
 
-                    ime_state.output = String::new();
+                    ime_state.clear_output();
                     window.set_visible(false);
                 } else if keybinding.matches(&SETTINGS.keys.accept) {
-                    info!("accepting: {}", converter.output);
+                    info!("accepting: {}", converter.preedit());
 
                     xsession.ungrab_keyboard().unwrap();
 
+                    let kana = converter.accept();
                     let output = if let Some(output) =
                         ime_state.conversions.get(ime_state.selected_conversion)
                     {
                         output
                     } else {
-                        &converter.output
+                        &kana
                     };
 
                     if let Err(e) = output::output(output) {
                         error!("{:?}", e);
                     }
 
+                    // remember the chosen conversion so it ranks higher next time, only for
+                    // dictionary words as generated ones such as dates would go stale
+                    if ime_state.selected_conversion < ime_state.dictionary_conversions {
+                        let recorded = db::get_connection()
+                            .and_then(|conn| db::record_selection(&conn, &kana, output));
+                        if let Err(e) = recorded {
+                            error!("{:?}", e);
+                        }
+                    }
+
                     xsession.grab_keyboard().unwrap();
 
-                    converter.accept();
                     ime_state.clear_conversions();
                     update_size(&gui_state, &ime_state, &window);
 
-                    ime_state.output = String::new();
+                    ime_state.clear_output();
                     window.set_visible(false);
                 } else if keybinding.matches(&SETTINGS.keys.delete) {
                     converter.del_char();
@@ -173,13 +212,78 @@ pub async fn run() {
                     ime_state.clear_conversions();
                     update_size(&gui_state, &ime_state, &window);
 
-                    ime_state.output = converter.output.clone();
-                    info!("deleted {:?}", converter.output);
+                    ime_state.set_preedit(&converter);
+                    info!("deleted {:?}", converter.preedit());
+
+                    // if input empty now close window
+                    if converter.is_empty() {
+                        window.set_visible(false);
+                    }
+                } else if keybinding.matches(&SETTINGS.keys.delete_forward) {
+                    converter.del_char_forward();
+
+                    // we changed input so clear conversions
+                    ime_state.clear_conversions();
+                    update_size(&gui_state, &ime_state, &window);
+
+                    ime_state.set_preedit(&converter);
+                    info!("deleted forward {:?}", converter.preedit());
 
                     // if input empty now close window
-                    if ime_state.output.is_empty() {
+                    if converter.is_empty() {
                         window.set_visible(false);
                     }
+                } else if keybinding.matches(&SETTINGS.keys.move_left)
+                    || keybinding.matches(&SETTINGS.keys.move_right)
+                    || keybinding.matches(&SETTINGS.keys.move_home)
+                    || keybinding.matches(&SETTINGS.keys.move_end)
+                {
+                    if keybinding.matches(&SETTINGS.keys.move_left) {
+                        converter.move_left();
+                    } else if keybinding.matches(&SETTINGS.keys.move_right) {
+                        converter.move_right();
+                    } else if keybinding.matches(&SETTINGS.keys.move_home) {
+                        converter.move_home();
+                    } else if keybinding.matches(&SETTINGS.keys.move_end) {
+                        converter.move_end();
+                    }
+
+                    // moving the caret goes back to editing the raw kana
+                    ime_state.clear_conversions();
+                    update_size(&gui_state, &ime_state, &window);
+
+                    ime_state.set_preedit(&converter);
+                    info!("caret {}", converter.preedit().caret());
+                } else if let Some(modify) = SETTINGS.keys.kana_modifier(&keybinding) {
+                    modify(&mut converter);
+
+                    // we changed input so clear conversions
+                    ime_state.clear_conversions();
+                    update_size(&gui_state, &ime_state, &window);
+
+                    ime_state.set_preedit(&converter);
+                    info!("modified kana {:?}", converter.preedit());
+                } else if let Some(to) = SETTINGS.keys.transliteration(&keybinding) {
+                    converter.transliterate(to);
+
+                    // we changed input so clear conversions
+                    ime_state.clear_conversions();
+                    update_size(&gui_state, &ime_state, &window);
+
+                    ime_state.set_preedit(&converter);
+                    info!("transliterated to {:?} {:?}", to, converter.preedit());
+                } else if keybinding.matches(&SETTINGS.keys.alphanumeric) {
+                    let input_mode = match converter.input_mode() {
+                        InputMode::FullWidthAlphanumeric => InputMode::Kana,
+                        _ => InputMode::FullWidthAlphanumeric,
+                    };
+                    converter.set_input_mode(input_mode);
+                    info!("input mode {:?}", input_mode);
+
+                    // switching modes converts any pending romaji
+                    if ime_state.conversions.is_empty() {
+                        ime_state.set_preedit(&converter);
+                    }
                 } else if keybinding.matches(&SETTINGS.keys.cancel) {
                     if ime_state.conversions.is_empty() {
                         // if conversion already empty, close conversion window and reset entire conversion
@@ -188,7 +292,7 @@ pub async fn run() {
                         ime_state.clear_conversions();
                         update_size(&gui_state, &ime_state, &window);
 
-                        ime_state.output = String::new();
+                        ime_state.clear_output();
                         window.set_visible(false);
                     } else {
                         // otherwise cancel out of conversion
@@ -197,7 +301,7 @@ pub async fn run() {
                         update_size(&gui_state, &ime_state, &window);
 
                         // bring back raw kana
-                        ime_state.output = converter.output.clone();
+                        ime_state.set_preedit(&converter);
                     }
                 } else if keybinding.matches(&SETTINGS.keys.next_conversion)
                     || keybinding.matches(&SETTINGS.keys.prev_conversion)
@@ -215,16 +319,32 @@ pub async fn run() {
                         info!("new index {}", ime_state.selected_conversion);
                     } else {
                         // conversion not done, populate conversion options list
+                        converter.flush();
                         let db_conn = db::get_connection().unwrap();
-                        let kana = &converter.output;
-                        let converted = db::search(&db_conn, kana).unwrap();
+                        let kana = converter.preedit().to_string();
+                        let converted = db::search(&db_conn, &kana).unwrap();
 
                         for entry in converted {
-                            ime_state.conversions.push(entry.k_ele);
+                            let gloss = entry.short_gloss().map(String::from);
+                            ime_state.push_conversion(entry.k_ele, gloss);
+                        }
+                        ime_state.dictionary_conversions = ime_state.conversions.len();
+
+                        // candidates generated from the input, such as numbers
+                        for candidate in candidates::generate(&kana) {
+                            if !ime_state.conversions.contains(&candidate) {
+                                ime_state.push_conversion(candidate, None);
+                            }
                         }
 
                         // always push exactly what we typed
-                        ime_state.conversions.push(kana.clone());
+                        ime_state.push_conversion(kana.clone(), None);
+
+                        // as well as the full-width form of the typed keys
+                        let fullwidth = to_fullwidth_ascii(&converter.typed());
+                        if fullwidth != kana && !ime_state.conversions.contains(&fullwidth) {
+                            ime_state.push_conversion(fullwidth, None);
+                        }
 
                         // set current to beginning
                         ime_state.selected_conversion = 0;
@@ -235,6 +355,7 @@ pub async fn run() {
                         .get(ime_state.selected_conversion)
                         .unwrap()
                         .to_string();
+                    ime_state.preedit = None;
                     update_size(&gui_state, &ime_state, &window);
                 } else {
                     // otherwise feed input directly to converter
@@ -246,8 +367,8 @@ pub async fn run() {
                             // we changed input so clear conversions
                             ime_state.clear_conversions();
 
-                            ime_state.output = converter.output.clone();
-                            info!("inputted {:?}", converter.output);
+                            ime_state.set_preedit(&converter);
+                            info!("inputted {:?}", converter.preedit());
 
                             // show completion box
                             window.set_visible(true);
@@ -265,8 +386,19 @@ fn update_size(gui_state: &GUIState, ime_state: &IMEState, window: &Window) {
 
     // let min_font_size = scaled_font.h_advance(gui_state.font.glyph_id('あ')); // value of 27.62431
 
-    // calculate max horizontal
-    let total_width = 300.;
+    // calculate max horizontal, wide enough for the longest conversion and its gloss
+    let total_width = ime_state
+        .conversions
+        .iter()
+        .enumerate()
+        .map(|(i, conversion)| {
+            let gloss = ime_state.glosses.get(i).map_or("", String::as_str);
+            gui_state.conversion_width(conversion, gloss)
+        })
+        .chain(std::iter::once(
+            gui_state.advance(&ime_state.output, gui_state.font_scale),
+        ))
+        .fold(300., f32::max);
 
     // calculate max vertical
     let total_height = scaled_font.height() * (ime_state.conversions.len() as f32 + 1.0);
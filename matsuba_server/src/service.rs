@@ -1,18 +1,21 @@
 use matsuba_grpc::matsuba_server::Matsuba;
 pub use matsuba_grpc::matsuba_server::MatsubaServer;
 use matsuba_grpc::{
-    ConvertRequest, ConvertResponse, FetchRequest, FetchResponse, GetStateRequest,
-    GetStateResponse, SetStateHenkanRequest, SetStateHenkanResponse, SetStateMuhenkanRequest,
-    SetStateMuhenkanResponse,
+    AddWordRequest, AddWordResponse, ConvertRequest, ConvertResponse, FetchRequest, FetchResponse,
+    GetStateRequest, GetStateResponse, Glosses, ListWordsRequest, ListWordsResponse,
+    RemoveWordRequest, RemoveWordResponse, RomajiRequest, RomajiResponse, SetStateHenkanRequest,
+    SetStateHenkanResponse, SetStateMuhenkanRequest, SetStateMuhenkanResponse, UserWord,
 };
 use std::collections::HashSet;
 use tonic::{Code, Request, Response, Status};
 
-use matsuba_common::converter::Converter;
+use matsuba_common::conversion::Romanization;
+use matsuba_common::romanizer::{LongVowels, Romanizer};
+use matsuba_common::width::to_fullwidth_ascii;
 
 use crate::config::SETTINGS;
 
-use super::{db, xmlparse};
+use super::{candidates, db, xmlparse};
 
 pub struct MatsubaService {}
 
@@ -24,7 +27,7 @@ impl Matsuba for MatsubaService {
     ) -> Result<Response<ConvertResponse>, Status> {
         let request = request.get_ref();
 
-        let mut c = Converter::new();
+        let mut c = SETTINGS.converter();
 
         let conn = db::get_connection().or(Err(Status::new(
             Code::Internal,
@@ -35,23 +38,89 @@ impl Matsuba for MatsubaService {
         for ch in request.raw.chars() {
             c.input_char(ch);
         }
-        let kana = c.accept();
+        c.flush();
+        let typed = c.typed();
+
+        // romaji left at the end of the input is reported instead of being converted
+        let preedit = c.preedit();
+        let kana = preedit.converted();
+        let pending = preedit.pending;
 
         // if kana flag is passed, don't do any more conversion
         if request.kana_only {
             return Ok(Response::new(ConvertResponse {
                 converted: vec![kana],
+                pending,
+                glosses: vec![Glosses::default()],
             }));
         }
 
-        let converted = db::search(&conn, &kana)
-            .or(Err(Status::new(Code::Internal, "error querying database")))?
+        let entries = db::search(&conn, &kana)
+            .or(Err(Status::new(Code::Internal, "error querying database")))?;
+        // each candidate is kept with its glosses, which only dictionary entries have
+        let mut candidates = entries
             .iter()
-            .take(request.result_count as usize)
-            .map(|x| x.k_ele.clone())
-            .collect::<Vec<String>>();
+            .map(|x| {
+                let glosses = Glosses {
+                    senses: x
+                        .senses
+                        .iter()
+                        .map(|sense| sense.gloss.join("; "))
+                        .collect(),
+                };
+                (x.k_ele.clone(), glosses)
+            })
+            .collect::<Vec<(String, Glosses)>>();
+
+        // candidates generated from the input, such as numbers
+        candidates.extend(
+            candidates::generate(&kana)
+                .into_iter()
+                .map(|candidate| (candidate, Glosses::default())),
+        );
+
+        // also offer the full-width form of the raw input
+        let fullwidth = to_fullwidth_ascii(&typed);
+        if fullwidth != kana {
+            candidates.push((fullwidth, Glosses::default()));
+        }
+
+        // keep the first of candidates that are written the same
+        let mut seen = HashSet::new();
+        candidates.retain(|(candidate, _)| seen.insert(candidate.clone()));
+        candidates.truncate(request.result_count as usize);
+        let (converted, glosses) = candidates.into_iter().unzip();
+
+        Ok(Response::new(ConvertResponse {
+            converted,
+            pending,
+            glosses,
+        }))
+    }
+
+    async fn romaji(
+        &self,
+        request: Request<RomajiRequest>,
+    ) -> Result<Response<RomajiResponse>, Status> {
+        let request = request.get_ref();
+
+        let romanization = if request.romanization.is_empty() {
+            Romanization::default()
+        } else {
+            request
+                .romanization
+                .parse::<Romanization>()
+                .map_err(|e| Status::new(Code::InvalidArgument, e))?
+        };
+
+        let mut romanizer = Romanizer::new(romanization);
+        if request.mark_long_vowels {
+            romanizer.long_vowels = LongVowels::Marked;
+        }
 
-        Ok(Response::new(ConvertResponse { converted }))
+        Ok(Response::new(RomajiResponse {
+            romaji: romanizer.romanize(&request.kana),
+        }))
     }
 
     async fn fetch(
@@ -69,24 +138,20 @@ impl Matsuba for MatsubaService {
             "failed initializing database",
         )))?;
 
-        // TODO stupid how we convert hashset to vec and then back to hashset
-        let mut tags: HashSet<&str> = HashSet::new();
-        for tag in &request.tags {
-            tags.insert(tag);
-        }
-
-        let tempfile_path = std::path::Path::new(&SETTINGS.database.cache_dir).join("JMdict_e.gz");
-        let dict_path = std::path::Path::new(&SETTINGS.database.cache_dir).join("dict.xml"); // TODO is this dangerous?
-
-        match xmlparse::fetch_jmdict_xml(&tempfile_path, &dict_path).await {
-            Ok(()) => (),
+        // import a local copy of the dictionary if given, otherwise download it
+        let path = match request.path.is_empty() {
+            true => xmlparse::fetch_jmdict().await,
+            false => Ok(std::path::PathBuf::from(&request.path)),
+        };
+        let source = match path.and_then(|path| xmlparse::open_jmdict(&path)) {
+            Ok(source) => source,
             Err(e) => {
                 log::error!("{}", e);
                 return Err(Status::new(Code::Internal, "issue fetching dict"));
             }
-        }
+        };
 
-        xmlparse::parse_jmdict_xml(&mut conn, &dict_path, &tags)
+        xmlparse::parse_jmdict_xml(&mut conn, source)
             .or(Err(Status::new(Code::Internal, "issue parsing dict")))?;
 
         Ok(Response::new(FetchResponse {}))
@@ -115,4 +180,65 @@ impl Matsuba for MatsubaService {
         unimplemented!();
         Ok(Response::new(SetStateMuhenkanResponse {}))
     }
+
+    async fn add_word(
+        &self,
+        request: Request<AddWordRequest>,
+    ) -> Result<Response<AddWordResponse>, Status> {
+        let request = request.get_ref();
+        if request.reading.is_empty() || request.word.is_empty() {
+            return Err(Status::new(
+                Code::InvalidArgument,
+                "reading and word must not be empty",
+            ));
+        }
+
+        let conn = db::get_connection().or(Err(Status::new(
+            Code::Internal,
+            "could not establish connection to database",
+        )))?;
+
+        let added = db::add_user_word(&conn, &request.reading, &request.word)
+            .or(Err(Status::new(Code::Internal, "error adding word")))?;
+
+        Ok(Response::new(AddWordResponse { added }))
+    }
+
+    async fn remove_word(
+        &self,
+        request: Request<RemoveWordRequest>,
+    ) -> Result<Response<RemoveWordResponse>, Status> {
+        let request = request.get_ref();
+
+        let conn = db::get_connection().or(Err(Status::new(
+            Code::Internal,
+            "could not establish connection to database",
+        )))?;
+
+        let removed = db::remove_user_word(&conn, &request.reading, &request.word)
+            .or(Err(Status::new(Code::Internal, "error removing word")))?;
+
+        Ok(Response::new(RemoveWordResponse { removed }))
+    }
+
+    async fn list_words(
+        &self,
+        _request: Request<ListWordsRequest>,
+    ) -> Result<Response<ListWordsResponse>, Status> {
+        let conn = db::get_connection().or(Err(Status::new(
+            Code::Internal,
+            "could not establish connection to database",
+        )))?;
+
+        let words = db::list_user_words(&conn)
+            .or(Err(Status::new(Code::Internal, "error querying database")))?
+            .into_iter()
+            .map(|entry| UserWord {
+                reading: entry.r_ele,
+                word: entry.k_ele,
+            })
+            .collect();
+
+        Ok(Response::new(ListWordsResponse { words }))
+    }
 }
@@ -1,22 +1,25 @@
-use roxmltree::{Document, Node, ParsingOptions};
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt::Display;
-use std::io::{Read, Write};
-use std::path::Path;
-use std::process::Command;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
 use std::vec::Vec;
 
+use flate2::bufread::GzDecoder;
 use log::debug;
 
-use super::db::insert_entry;
-use super::db::{DBConnection, Entry};
+use super::db::{clear_entries, insert_entry, insert_senses};
+use super::db::{DBConnection, Entry, Sense};
+use super::jmdict::{JmdictEntry, JmdictReader};
 
+use crate::config::SETTINGS;
 use crate::error::BoxResult;
 
 #[derive(Debug)]
 pub enum XmlError {
     Fetch(String),
+    Read(String),
     KebNotExist,
 }
 impl Error for XmlError {}
@@ -24,136 +27,297 @@ impl Display for XmlError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Fetch(e) => write!(f, "fetch error: {:?}", e),
+            Self::Read(e) => write!(f, "read error: {:?}", e),
             Self::KebNotExist => write!(f, "keb does not exist"),
         }
     }
 }
 
-/// Downloads jmdict
-pub async fn fetch_jmdict_xml(tempfile_path: &Path, target_path: &Path) -> Result<(), XmlError> {
-    use std::fs::File;
-
+/// Downloads jmdict, compressed with gzip, into the cache directory and returns its path
+///
+/// The download is written to disk as it arrives rather than kept in memory.
+pub async fn fetch_jmdict() -> Result<PathBuf, XmlError> {
     const DICT_URL: &str = "http://ftp.edrdg.org/pub/Nihongo/JMdict_e.gz";
 
-    let body = reqwest::get(DICT_URL)
-        .await
-        .map_err(|e| XmlError::Fetch(e.to_string()))?
-        .bytes()
+    let cache_dir = Path::new(&SETTINGS.database.cache_dir);
+    let path = cache_dir.join("JMdict_e.gz");
+    // downloaded under another name first so that an interrupted download is never imported
+    let partial = cache_dir.join("JMdict_e.gz.part");
+
+    let fetch_err = |e: &dyn Display| XmlError::Fetch(e.to_string());
+    let mut response = reqwest::get(DICT_URL)
         .await
-        .map_err(|e| XmlError::Fetch(e.to_string()))?;
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| fetch_err(&e))?;
+    let mut file = File::create(&partial).map_err(|e| fetch_err(&e))?;
+    while let Some(chunk) = response.chunk().await.map_err(|e| fetch_err(&e))? {
+        file.write_all(&chunk).map_err(|e| fetch_err(&e))?;
+    }
+    std::fs::rename(&partial, &path).map_err(|e| fetch_err(&e))?;
 
-    let mut file = File::create(tempfile_path).map_err(|e| XmlError::Fetch(e.to_string()))?;
-    file.write_all(body.as_ref())
-        .map_err(|e| XmlError::Fetch(e.to_string()))?;
+    Ok(path)
+}
 
-    // TODO use library to do this instead
-    let decoded = Command::new("gzip")
-        .args(vec!["-dc", tempfile_path.to_str().unwrap()])
-        .output()
-        .map_err(|e| XmlError::Fetch(e.to_string()))?;
+/// Opens a copy of jmdict, such as `JMdict_e`, `JMdict_e.gz` or the multilingual `JMdict`
+///
+/// Files compressed with gzip are decompressed as they are read.
+pub fn open_jmdict(path: &Path) -> Result<Box<dyn BufRead>, XmlError> {
+    let read_err = |e: std::io::Error| XmlError::Read(format!("{}: {}", path.display(), e));
 
-    let mut file = File::create(target_path).map_err(|e| XmlError::Fetch(e.to_string()))?;
-    file.write_all(&decoded.stdout).unwrap();
+    let mut reader = BufReader::new(File::open(path).map_err(read_err)?);
+    // gzip is detected from its magic number, as the file name may not say
+    let compressed = reader
+        .fill_buf()
+        .map_err(read_err)?
+        .starts_with(&[0x1f, 0x8b]);
+    Ok(match compressed {
+        true => Box::new(BufReader::new(GzDecoder::new(reader))),
+        false => Box::new(reader),
+    })
+}
 
+/// Replaces the dictionary with the entries of jmdict
+///
+/// Entries are parsed and inserted one at a time, so memory use does not depend on the size of
+/// the dictionary.
+pub fn parse_jmdict_xml(conn: &mut DBConnection, source: impl BufRead) -> BoxResult<()> {
+    let tx = conn.transaction()?;
+    clear_entries(&tx)?;
+    for entry in JmdictReader::new(source) {
+        parse_entry(&tx, &entry?)?;
+    }
+
+    tx.commit()?;
     Ok(())
 }
 
-pub fn parse_jmdict_xml(
-    conn: &mut DBConnection,
-    path: &Path,
-    tags: &HashSet<&str>,
-) -> BoxResult<()> {
-    let text = std::fs::read_to_string(path).unwrap();
-    let opt = ParsingOptions { allow_dtd: true };
-    let doc = Document::parse_with_options(&text, opt)?;
+/// Score of the priority markers of a kanji or reading element, higher for more common words
+///
+/// Words in the frequency lists of the JMdict sources (news1, ichi1, spec1 and gai1) score higher
+/// than the rest of each list, and the nfXX rank of words from the news list adds a bonus that
+/// is highest for nf01.
+pub fn priority_score<'a>(markers: impl IntoIterator<Item = &'a str>) -> u32 {
+    markers
+        .into_iter()
+        .map(|marker| match marker {
+            "news1" | "ichi1" | "spec1" | "gai1" => 50,
+            "news2" | "ichi2" | "spec2" | "gai2" => 10,
+            _ => marker
+                .strip_prefix("nf")
+                .and_then(|rank| rank.parse::<u32>().ok())
+                .map(|rank| 49u32.saturating_sub(rank))
+                .unwrap_or(0),
+        })
+        .sum()
+}
+
+fn parse_entry(conn: &DBConnection, entry: &JmdictEntry) -> BoxResult<()> {
+    let mut entries: Vec<Entry> = Vec::new();
+    // priority of each kanji element, which is added to the priority of its readings
+    let mut keb_priority: HashMap<&str, u32> = HashMap::new();
 
-    // JMdict element node should be the last child
-    let root = doc.root().last_child().unwrap();
+    for k_ele in &entry.k_ele {
+        // ignore duplicate
+        keb_priority
+            .entry(k_ele.keb.as_str())
+            .or_insert_with(|| priority_score(k_ele.ke_pri.iter().map(String::as_str)));
+    }
 
-    let tx = conn.transaction()?;
-    for node in root.children().filter(|n| n.is_element()) {
-        parse_entry(&tx, &node, tags)?;
+    for r_ele in &entry.r_ele {
+        let reb_priority = priority_score(r_ele.re_pri.iter().map(String::as_str));
+
+        // reading only applies to the kanji elements in re_restr, or all of them if there are none
+        let add_reading_to: Vec<&str> = match r_ele.re_restr.is_empty() {
+            true => entry.k_ele.iter().map(|k| k.keb.as_str()).collect(),
+            false => r_ele.re_restr.iter().map(String::as_str).collect(),
+        };
+        for keb in add_reading_to {
+            let priority = keb_priority.get(keb).ok_or(XmlError::KebNotExist)? + reb_priority;
+            let entry = Entry {
+                ent_seq: Some(entry.ent_seq),
+                ..Entry::new(r_ele.reb.clone(), keb.to_string(), priority)
+            };
+            if !entries.contains(&entry) {
+                entries.push(entry);
+            }
+        }
     }
 
-    tx.commit()?;
+    for entry in entries.iter() {
+        debug!("{} - {} ({})", entry.k_ele, entry.r_ele, entry.frequency);
+        insert_entry(conn, entry)?;
+    }
+
+    // entries without kanji are never converted to, so their senses are not needed
+    if !entries.is_empty() {
+        let senses: Vec<Sense> = entry
+            .sense
+            .iter()
+            .map(|sense| Sense {
+                stagk: sense.stagk.clone(),
+                gloss: sense.gloss.clone(),
+                pos: sense.pos.clone(),
+                misc: sense.misc.clone(),
+                field: sense.field.clone(),
+            })
+            .collect();
+        insert_senses(conn, entry.ent_seq, &senses)?;
+    }
     Ok(())
 }
 
-fn parse_entry(conn: &DBConnection, entry_node: &Node, tags: &HashSet<&str>) -> BoxResult<()> {
-    let mut entries: HashMap<String, Vec<Entry>> = HashMap::new();
-
-    for elem in entry_node.children().filter(|n| n.is_element()) {
-        match elem.tag_name().name() {
-            "dial" | "field" | "ke_inf" | "misc" | "pos" | "re_inf" => {
-                // check if we are accepting the tag
-                let tag = elem.text().unwrap();
-                if tags.get(tag).is_none() {
-                    continue;
-                }
-            }
-            "k_ele" => {
-                // parse kanji element
-
-                // keb guaranteed to exist
-                let keb_text = elem
-                    .children()
-                    .find(|n| n.tag_name().name() == "keb")
-                    .unwrap()
-                    .text()
-                    .unwrap();
-
-                // ignore duplicate (could also use nightly 'try_insert')
-                if entries.contains_key(keb_text) {
-                    // return Err(SimpleError::new(""));
-                    break;
-                }
-
-                entries.insert(keb_text.to_string(), Vec::new());
-            }
-            "r_ele" => {
-                // parse reading
-
-                // reb guaranteed to exist
-                let reb_text = elem
-                    .children()
-                    .find(|n| n.tag_name().name() == "reb")
-                    .unwrap()
-                    .text()
-                    .unwrap();
-
-                // check for re_restr (reading only applies to specific kanji elements)
-                let mut add_reading_to: Vec<&str> = Vec::new();
-                for re_restr_node in elem
-                    .children()
-                    .filter(|n| n.tag_name().name() == "re_restr")
-                {
-                    let re_restr_text = re_restr_node.text().unwrap();
-                    add_reading_to.push(re_restr_text);
-                }
-
-                // if no re_restr, assume all
-                if add_reading_to.is_empty() {
-                    for (keb, conv) in entries.iter_mut() {
-                        conv.push(Entry::new(reb_text.to_string(), keb.to_string()));
-                    }
-                } else {
-                    for keb in add_reading_to {
-                        entries
-                            .get_mut(keb)
-                            .ok_or(XmlError::KebNotExist)?
-                            .push(Entry::new(reb_text.to_string(), keb.to_string()));
-                    }
-                }
-            }
-            _ => {}
+#[cfg(test)]
+mod tests {
+
+    use std::io::{Read, Write};
+
+    use flate2::{write::GzEncoder, Compression};
+    use rusqlite::Connection;
+
+    use super::{open_jmdict, parse_entry, parse_jmdict_xml, priority_score, XmlError};
+    use crate::db;
+    use crate::jmdict::JmdictReader;
+
+    const JMDICT: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+        <!DOCTYPE JMdict [
+        <!ENTITY n \"noun (common) (futsuumeishi)\">
+        ]>
+        <JMdict>
+            <entry>
+                <ent_seq>1000001</ent_seq>
+                <k_ele><keb>箸</keb><ke_pri>ichi1</ke_pri></k_ele>
+                <r_ele><reb>はし</reb><re_pri>ichi1</re_pri></r_ele>
+                <sense><pos>&n;</pos><gloss>chopsticks</gloss></sense>
+            </entry>
+            <entry>
+                <ent_seq>1000002</ent_seq>
+                <k_ele><keb>端</keb><ke_pri>ichi1</ke_pri><ke_pri>news2</ke_pri><ke_pri>nf26</ke_pri></k_ele>
+                <r_ele><reb>はし</reb><re_pri>ichi1</re_pri><re_pri>news2</re_pri><re_pri>nf26</re_pri></r_ele>
+            </entry>
+            <entry>
+                <ent_seq>1000003</ent_seq>
+                <k_ele><keb>觜</keb></k_ele>
+                <r_ele><reb>はし</reb></r_ele>
+            </entry>
+            <entry>
+                <ent_seq>1000004</ent_seq>
+                <k_ele><keb>橋</keb><ke_pri>ichi1</ke_pri><ke_pri>news1</ke_pri><ke_pri>nf05</ke_pri></k_ele>
+                <r_ele><reb>はし</reb><re_pri>ichi1</re_pri><re_pri>news1</re_pri><re_pri>nf05</re_pri></r_ele>
+                <sense><gloss>bridge</gloss><gloss xml:lang=\"ger\">Brücke</gloss></sense>
+            </entry>
+        </JMdict>";
+
+    fn kanji(conn: &Connection, reading: &str) -> Vec<String> {
+        db::search(conn, reading)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.k_ele)
+            .collect()
+    }
+
+    #[test]
+    fn priority() {
+        assert_eq!(priority_score([]), 0);
+        assert_eq!(priority_score(["ichi1"]), 50);
+        assert_eq!(priority_score(["news2", "nf30"]), 29);
+        assert_eq!(priority_score(["news1", "ichi1", "nf01"]), 148);
+        assert_eq!(priority_score(["nfxx"]), 0);
+        assert!(priority_score(["spec1"]) > priority_score(["spec2"]));
+        assert!(priority_score(["news1", "nf02"]) > priority_score(["news1", "nf20"]));
+    }
+
+    #[test]
+    fn ranking() {
+        let xml = "<JMdict>
+            <entry>
+                <k_ele><keb>箸</keb><ke_pri>ichi1</ke_pri></k_ele>
+                <r_ele><reb>はし</reb><re_pri>ichi1</re_pri></r_ele>
+            </entry>
+            <entry>
+                <k_ele><keb>端</keb><ke_pri>ichi1</ke_pri><ke_pri>news2</ke_pri><ke_pri>nf26</ke_pri></k_ele>
+                <r_ele><reb>はし</reb><re_pri>ichi1</re_pri><re_pri>news2</re_pri><re_pri>nf26</re_pri></r_ele>
+            </entry>
+            <entry>
+                <k_ele><keb>觜</keb></k_ele>
+                <r_ele><reb>はし</reb></r_ele>
+            </entry>
+            <entry>
+                <k_ele><keb>橋</keb><ke_pri>ichi1</ke_pri><ke_pri>news1</ke_pri><ke_pri>nf05</ke_pri></k_ele>
+                <r_ele><reb>はし</reb><re_pri>ichi1</re_pri><re_pri>news1</re_pri><re_pri>nf05</re_pri></r_ele>
+            </entry>
+        </JMdict>";
+        let conn = Connection::open_in_memory().unwrap();
+        db::init(&conn).unwrap();
+        for entry in JmdictReader::new(xml.as_bytes()) {
+            parse_entry(&conn, &entry.unwrap()).unwrap();
         }
+
+        let kanji = db::search(&conn, "はし")
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.k_ele)
+            .collect::<Vec<_>>();
+        assert_eq!(kanji, vec!["橋", "端", "箸", "觜"]);
     }
 
-    for group in entries.values() {
-        for entry in group.iter() {
-            debug!("{} - {}", entry.k_ele, entry.r_ele);
-            insert_entry(conn, entry)?;
+    #[test]
+    fn glosses() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        db::init(&conn).unwrap();
+        parse_jmdict_xml(&mut conn, JMDICT.as_bytes()).unwrap();
+        let glosses = db::search(&conn, "はし")
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.k_ele.clone(), entry.short_gloss().map(String::from)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            glosses,
+            vec![
+                ("橋".to_string(), Some("bridge".to_string())),
+                ("端".to_string(), None),
+                ("箸".to_string(), Some("chopsticks".to_string())),
+                ("觜".to_string(), None),
+            ]
+        );
+
+        let entries = db::search(&conn, "はし").unwrap();
+        assert_eq!(entries[2].senses[0].pos, vec!["n"]);
+        assert_eq!(entries[2].ent_seq, Some(1000001));
+    }
+
+    #[test]
+    fn open() {
+        let dir = std::env::temp_dir().join(format!("matsuba-xmlparse-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(JMDICT.as_bytes()).unwrap();
+        let compressed = dir.join("JMdict_e");
+        std::fs::write(&compressed, encoder.finish().unwrap()).unwrap();
+        let plain = dir.join("JMdict_e.xml");
+        std::fs::write(&plain, JMDICT).unwrap();
+
+        for path in [&compressed, &plain] {
+            let mut text = String::new();
+            open_jmdict(path)
+                .unwrap()
+                .read_to_string(&mut text)
+                .unwrap();
+            assert_eq!(text, JMDICT);
+        }
+
+        // importing again replaces the dictionary instead of adding to it
+        let mut conn = Connection::open_in_memory().unwrap();
+        db::init(&conn).unwrap();
+        for path in [&compressed, &plain] {
+            parse_jmdict_xml(&mut conn, open_jmdict(path).unwrap()).unwrap();
+            assert_eq!(kanji(&conn, "はし"), vec!["橋", "端", "箸", "觜"]);
         }
+        assert!(matches!(
+            open_jmdict(&dir.join("missing")),
+            Err(XmlError::Read(_))
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
-    Ok(())
 }